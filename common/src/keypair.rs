@@ -0,0 +1,57 @@
+//! Key files in the formats the binaries accept with `--keypair-format`.
+
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum KeypairFormat {
+    /// JSON array of the 64 secret key bytes, as written by solana-keygen
+    JsonArray,
+    /// Base58 string of the 64 secret key bytes, as exported by wallets
+    Base58,
+    /// Hex string of the 64 secret key bytes
+    Hex,
+}
+
+/// Length of a secret key with its public half.
+const KEYPAIR_LEN: usize = 64;
+
+/// Reads the key file at `path` and builds the keypair of its 64 bytes with
+/// `from_bytes`, the constructor of the SDK version of the caller.
+pub fn read_keypair<K, E: std::fmt::Display>(
+    path: &str,
+    format: KeypairFormat,
+    from_bytes: impl FnOnce(&[u8]) -> Result<K, E>,
+) -> anyhow::Result<K> {
+    let error = |e: &dyn std::fmt::Display| {
+        anyhow::Error::msg(format!("failed to read keypair {}: {}", path, e))
+    };
+    let content = std::fs::read_to_string(path).map_err(|e| error(&e))?;
+    let bytes = match format {
+        KeypairFormat::JsonArray => serde_json::from_str::<Vec<u8>>(&content)
+            .map_err(|_| error(&"the file is not a JSON array of bytes"))?,
+        KeypairFormat::Base58 => bs58::decode(content.trim())
+            .into_vec()
+            .map_err(|_| error(&"the file is not a base58 string"))?,
+        KeypairFormat::Hex => {
+            decode_hex(content.trim()).ok_or_else(|| error(&"the file is not a hex string"))?
+        }
+    };
+    if bytes.len() != KEYPAIR_LEN {
+        return Err(error(&format!(
+            "expected {} secret key bytes, the file holds {}",
+            KEYPAIR_LEN,
+            bytes.len()
+        )));
+    }
+    from_bytes(&bytes).map_err(|e| error(&e))
+}
+
+pub fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,6 @@
+//! Helpers shared by the multisig client and `solana-signer`, independent of
+//! the Solana SDK version each of them builds against.
+
+pub mod keypair;
+pub mod multisig;
+pub mod offchain;
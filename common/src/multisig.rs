@@ -0,0 +1,15 @@
+//! What both binaries know of the multisig program.
+
+/// Program id used when neither `--pid` nor the profile sets one.
+pub const DEFAULT_PID: &str = "AAHT26ecV3FEeFmL2gDZW6FfEqjPkghHbAkNZGqwT8Ww"; // Devnet: msigUdDBsR4zSUYqYEDrc1LcgtmuSDDM7KxpRUXNC6U
+
+/// Instructions of the multisig program, named as in the program source.
+pub const INSTRUCTIONS: [&str; 7] = [
+    "create_multisig",
+    "create_transaction",
+    "approve",
+    "set_owners_and_change_threshold",
+    "set_owners",
+    "change_threshold",
+    "execute_transaction",
+];
@@ -0,0 +1,35 @@
+//! Texts of off-chain messages signed over an application domain, so that
+//! the signature of one application can't be replayed to another.
+
+/// Marks the first line of an off-chain message as its domain.
+pub const DOMAIN_PREFIX: &str = "domain: ";
+
+pub fn parse_domain(s: &str) -> anyhow::Result<String> {
+    if s.is_empty() || s.contains('\n') {
+        return Err(anyhow::Error::msg(
+            "expected a non-empty single-line domain",
+        ));
+    }
+    Ok(s.to_string())
+}
+
+/// Text of an off-chain message signed over `domain`, on a line of its own
+/// before the message.
+pub fn offchain_text(domain: Option<&str>, message: &str) -> String {
+    match domain {
+        Some(domain) => format!("{}{}\n{}", DOMAIN_PREFIX, domain, message),
+        None => message.to_string(),
+    }
+}
+
+/// Refuses a message without a domain whose first line reads like one, as
+/// it would verify as a message of that domain.
+pub fn check_domainless(message: &str) -> anyhow::Result<()> {
+    if message.starts_with(DOMAIN_PREFIX) {
+        return Err(anyhow::Error::msg(format!(
+            "the message starts like a domain, pass it with --domain instead of \"{}\"",
+            DOMAIN_PREFIX
+        )));
+    }
+    Ok(())
+}
@@ -1,19 +1,40 @@
+use std::cell::RefCell;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
 
-use anchor_client::Cluster;
+use anchor_client::{
+    solana_client::rpc_client::RpcClient,
+    solana_sdk::{account::Account, pubkey::Pubkey as RpcPubkey},
+    Cluster,
+};
 use base64::{prelude::BASE64_STANDARD, Engine};
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use crossterm::style::{style, Stylize};
+use serde::Deserialize;
+use serum_common::keypair::KeypairFormat;
+use serum_common::multisig::DEFAULT_PID;
+use serum_common::offchain::{self, offchain_text, parse_domain};
 use solana_remote_wallet::{
     ledger::LedgerWallet,
     locator::Manufacturer,
-    remote_wallet::{RemoteWallet, RemoteWalletError},
+    remote_wallet::{RemoteWallet, RemoteWalletError, RemoteWalletManager},
 };
 use solana_sdk::{
     derivation_path::DerivationPath,
+    hash::hash,
+    message::Message,
+    native_token::lamports_to_sol,
+    offchain_message::OffchainMessage,
     pubkey::Pubkey,
-    signature::Keypair,
-    signer::{EncodableKey, Signer, SignerError},
+    sanitize::Sanitize,
+    signature::{Keypair, Signature},
+    signer::{Signer, SignerError},
+    system_instruction::SystemInstruction,
+    transaction::Transaction,
 };
 
 #[derive(Parser)]
@@ -22,27 +43,361 @@ struct Cli {
     ledger: bool,
     #[arg(short = 'n', long = "account-number")]
     account_number: Option<u32>,
+    /// Give up if the signing isn't confirmed on the Ledger within this many seconds
+    #[arg(long = "ledger-confirm-timeout", requires = "ledger")]
+    ledger_confirm_timeout: Option<u64>,
+    /// Reconnect this many times when the Ledger drops its USB connection
+    #[arg(long = "ledger-retries", default_value_t = 3, requires = "ledger")]
+    ledger_retries: u32,
 
     #[arg(long = "cluster", default_value_t = Cluster::Devnet)]
     cluster: Cluster,
 
     #[arg(short = 'k', long = "private-key")]
     key_file: Option<String>,
+    /// Encoding of the key file
+    #[arg(long = "keypair-format", value_enum, default_value_t = KeypairFormat::JsonArray)]
+    keypair_format: KeypairFormat,
+
+    /// Sign the input as an off-chain message text instead of a base64 transaction message
+    #[arg(long = "offchain", default_value_t = false)]
+    offchain: bool,
+    /// Application domain prefixed to the off-chain message, as with the client's
+    /// `SignOffchain --domain`
+    #[arg(long = "domain", requires = "offchain", value_parser = parse_domain)]
+    domain: Option<String>,
+
+    /// Disable styled output, also implied by NO_COLOR or a non-terminal stdout
+    #[arg(long = "no-color", default_value_t = false)]
+    no_color: bool,
+
+    /// Print the SHA-256 hash of the signed message, to compare it with the one shown by the client
+    #[arg(long = "print-message-hash", default_value_t = false)]
+    print_message_hash: bool,
+
+    /// List the Ledger pubkeys of the account numbers 0..N instead of signing
+    #[arg(
+        long = "scan-accounts",
+        requires = "ledger",
+        conflicts_with = "account_number"
+    )]
+    scan_accounts: Option<u32>,
+
+    /// Sign the message of a signing request manifest written by the client, after checking it
+    #[arg(long = "request", conflicts_with_all = ["offchain", "transaction"])]
+    request: Option<PathBuf>,
+
+    /// Print a message given bare as a transaction with the signature in its slot, ready to be
+    /// submitted, or completed by the other signers
+    #[arg(
+        long = "assemble",
+        default_value_t = false,
+        conflicts_with = "offchain"
+    )]
+    assemble: bool,
 
-    transaction: String,
+    /// Print the pubkey of the key file or Ledger account instead of signing, with its SOL
+    /// balance on the cluster
+    #[arg(
+        long = "whoami",
+        default_value_t = false,
+        conflicts_with_all = ["offchain", "request", "assemble", "scan_accounts", "transaction"]
+    )]
+    whoami: bool,
+    /// Multisig to check the ownership of with --whoami
+    #[arg(long = "multisig", requires = "whoami")]
+    multisig: Option<Pubkey>,
+    /// Program owning the --multisig account [default: the devnet deployment]
+    #[arg(long = "pid", requires = "multisig")]
+    pid: Option<Pubkey>,
+    /// Don't query the cluster with --whoami
+    #[arg(long = "offline", default_value_t = false, requires = "whoami")]
+    offline: bool,
+
+    /// Encoding of the message or transaction [default: whichever of them decodes to a
+    /// message or transaction]
+    #[arg(long = "encoding", value_enum, conflicts_with = "offchain")]
+    encoding: Option<Encoding>,
+
+    /// Message, or a partially signed transaction to add the signature to, in base64 or base58
+    #[arg(required_unless_present_any = ["scan_accounts", "request", "whoami"])]
+    transaction: Option<String>,
+}
+
+/// Text encoding of the signed message or transaction.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Encoding {
+    Base64,
+    /// As printed by `--output-format partial-transaction` of the client
+    Base58,
+}
+
+impl Encoding {
+    fn decode(self, input: &str) -> Option<Vec<u8>> {
+        match self {
+            Encoding::Base64 => BASE64_STANDARD.decode(input).ok(),
+            Encoding::Base58 => bs58::decode(input).into_vec().ok(),
+        }
+    }
 }
 
+/// Signing request manifest, as written by `--output-format manifest` of the client.
+/// Only its message is trusted, what it says about the message is decoded
+/// from it again.
+#[derive(Deserialize)]
+struct SigningRequest {
+    version: u32,
+    message: String,
+    message_hash: String,
+    signers: Vec<RequiredSigner>,
+}
+
+#[derive(Deserialize)]
+struct RequiredSigner {
+    pubkey: String,
+}
+
+/// Names of the programs described in the signed messages.
+const KNOWN_PROGRAMS: [(Pubkey, &str); 8] = [
+    (solana_sdk::system_program::ID, "system"),
+    (solana_sdk::compute_budget::ID, "compute budget"),
+    (solana_sdk::stake::program::ID, "stake"),
+    (solana_sdk::vote::program::ID, "vote"),
+    (
+        solana_sdk::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"),
+        "token",
+    ),
+    (
+        solana_sdk::pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb"),
+        "token-2022",
+    ),
+    (
+        solana_sdk::pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL"),
+        "associated token account",
+    ),
+    (
+        solana_sdk::pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr"),
+        "memo",
+    ),
+];
+
+const SIGNING_REQUEST_VERSION: u32 = 2;
+
 #[derive(Clone)]
 struct LedgerSigner {
-    ledger: Rc<LedgerWallet>,
+    ledger: RefCell<Rc<LedgerWallet>>,
+    wallet_manager: Rc<RemoteWalletManager>,
+    retries: u32,
     derivation_path: DerivationPath,
+    confirm_timeout: Option<Duration>,
+}
+
+/// Version of the Ledger Solana app, as major, minor and patch.
+type AppVersion = (u64, u64, u64);
+
+/// Programs whose instructions the Ledger Solana app decodes and shows on its
+/// screen, with the app version that started to. Messages invoking any other
+/// can only be blind signed.
+const LEDGER_DISPLAYED_PROGRAMS: [(Pubkey, AppVersion); 8] = [
+    (solana_sdk::system_program::ID, (1, 0, 0)),
+    (solana_sdk::stake::program::ID, (1, 0, 0)),
+    (solana_sdk::vote::program::ID, (1, 0, 0)),
+    (
+        solana_sdk::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"),
+        (1, 1, 0),
+    ),
+    (
+        solana_sdk::pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL"),
+        (1, 1, 0),
+    ),
+    (
+        solana_sdk::pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr"),
+        (1, 1, 0),
+    ),
+    (solana_sdk::compute_budget::ID, (1, 3, 0)),
+    (
+        solana_sdk::pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb"),
+        (1, 4, 0),
+    ),
+];
+
+/// Latest app version `LEDGER_DISPLAYED_PROGRAMS` is known to be complete
+/// for. Newer apps may display more programs.
+const LEDGER_APP_LATEST_KNOWN: AppVersion = (1, 4, 1);
+
+/// Delay before reconnecting to a Ledger that dropped its connection.
+const LEDGER_RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// Whether the error means the Ledger dropped its USB connection.
+fn is_disconnect(e: &RemoteWalletError) -> bool {
+    matches!(
+        e,
+        RemoteWalletError::Hid(_) | RemoteWalletError::NoDeviceFound
+    )
+}
+
+fn find_ledger(
+    wallet_manager: &RemoteWalletManager,
+) -> Result<Rc<LedgerWallet>, RemoteWalletError> {
+    wallet_manager.update_devices()?;
+    let ledger_info = wallet_manager
+        .list_devices()
+        .into_iter()
+        .find(|wi| matches!(wi.manufacturer, Manufacturer::Ledger))
+        .ok_or(RemoteWalletError::NoDeviceFound)?;
+    wallet_manager.get_ledger(&ledger_info.host_device_path)
+}
+
+/// Finds the Ledger, looking for it again up to `retries` times while it is
+/// disconnected.
+fn connect_ledger(
+    wallet_manager: &RemoteWalletManager,
+    retries: u32,
+) -> Result<Rc<LedgerWallet>, RemoteWalletError> {
+    let mut attempt = 0;
+    loop {
+        match find_ledger(wallet_manager) {
+            Err(e) if is_disconnect(&e) && attempt < retries => {
+                attempt += 1;
+                eprintln!(
+                    "Ledger not found ({}), reconnect and unlock it and open the Solana app; \
+                     retrying in {} seconds ({} of {})",
+                    e,
+                    LEDGER_RECONNECT_DELAY.as_secs(),
+                    attempt,
+                    retries
+                );
+                thread::sleep(LEDGER_RECONNECT_DELAY);
+            }
+            result => return result,
+        }
+    }
+}
+
+impl LedgerSigner {
+    /// Version of the Solana app, read from its configuration when connecting.
+    fn app_version(&self) -> AppVersion {
+        let version = &self.ledger.borrow().version;
+        (version.major, version.minor, version.patch)
+    }
+
+    /// Checks whether the app version on the device can show the message
+    /// `data`. When it can't, it falls back to blind signing the message
+    /// hash, which must be enabled in the app settings.
+    fn check_display(&self, data: &[u8]) -> Result<(), SignerError> {
+        let Ok(message) = bincode::deserialize::<Message>(data) else {
+            return Ok(());
+        };
+        if message.sanitize().is_err() {
+            return Err(SignerError::Custom("malformed message".to_string()));
+        }
+        let version = self.app_version();
+        let mut undisplayed: Vec<Pubkey> = message
+            .instructions
+            .iter()
+            .map(|ix| message.account_keys[ix.program_id_index as usize])
+            .filter(|program| {
+                !LEDGER_DISPLAYED_PROGRAMS
+                    .iter()
+                    .any(|(id, since)| id == program && version >= *since)
+            })
+            .collect();
+        undisplayed.sort();
+        undisplayed.dedup();
+        if undisplayed.is_empty() {
+            return Ok(());
+        }
+        let programs = undisplayed
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let app = format!("{}.{}.{}", version.0, version.1, version.2);
+        let settings = self
+            .with_ledger(|ledger| ledger.get_settings())
+            .map_err(ledger_to_signer_error)?;
+        if version > LEDGER_APP_LATEST_KNOWN {
+            // Only the app knows what it displays, let it decide.
+            eprintln!(
+                "Warning: Solana app {} may not display the instructions of {}; if it shows the \
+                 message hash instead, check it against --print-message-hash{}",
+                app,
+                programs,
+                if settings.enable_blind_signing {
+                    ""
+                } else {
+                    ", and if it refuses, enable blind signing in its settings"
+                }
+            );
+            return Ok(());
+        }
+        if !settings.enable_blind_signing {
+            return Err(SignerError::Custom(format!(
+                "Solana app {} can't display the instructions of {} and blind signing is \
+                 disabled; enable it in the settings of the app, or update the app",
+                app, programs
+            )));
+        }
+        eprintln!(
+            "Warning: Solana app {} can't display the instructions of {} and shows the message \
+             hash instead; check it against --print-message-hash",
+            app, programs
+        );
+        Ok(())
+    }
+
+    /// Runs `operation` on the Ledger, reconnecting and running it again when
+    /// the device was disconnected.
+    fn with_ledger<T>(
+        &self,
+        operation: impl Fn(&LedgerWallet) -> Result<T, RemoteWalletError>,
+    ) -> Result<T, RemoteWalletError> {
+        let mut attempt = 0;
+        loop {
+            let ledger = self.ledger.borrow().clone();
+            match operation(&ledger) {
+                Err(e) if is_disconnect(&e) && attempt < self.retries => {
+                    attempt += 1;
+                    eprintln!(
+                        "Lost the Ledger connection ({}), reconnect it; retrying ({} of {})",
+                        e, attempt, self.retries
+                    );
+                    thread::sleep(LEDGER_RECONNECT_DELAY);
+                    *self.ledger.borrow_mut() =
+                        connect_ledger(&self.wallet_manager, self.retries - attempt)?;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Exits the process unless dropped within the timeout. The Ledger calls
+/// block without a way to cancel them.
+struct Watchdog {
+    _sender: mpsc::Sender<()>,
+}
+
+impl Watchdog {
+    fn start(timeout: Duration) -> Self {
+        let (sender, receiver) = mpsc::channel::<()>();
+        thread::spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = receiver.recv_timeout(timeout) {
+                eprintln!(
+                    "Error: the signing wasn't confirmed on the Ledger within {} seconds",
+                    timeout.as_secs()
+                );
+                std::process::exit(1);
+            }
+        });
+        Self { _sender: sender }
+    }
 }
 
 impl Signer for LedgerSigner {
     fn try_pubkey(&self) -> Result<Pubkey, solana_sdk::signer::SignerError> {
         Ok(self
-            .ledger
-            .get_pubkey(&self.derivation_path, false)
+            .with_ledger(|ledger| ledger.get_pubkey(&self.derivation_path, false))
             .map_err(ledger_to_signer_error)?
             .to_bytes()
             .into())
@@ -52,9 +407,13 @@ impl Signer for LedgerSigner {
         &self,
         message: &[u8],
     ) -> Result<solana_sdk::signature::Signature, solana_sdk::signer::SignerError> {
+        // Off-chain messages start with 0xff and are always displayed.
+        if message.first() != Some(&0xff) {
+            self.check_display(message)?;
+        }
+        let _watchdog = self.confirm_timeout.map(Watchdog::start);
         Ok(self
-            .ledger
-            .sign_message(&self.derivation_path, message)
+            .with_ledger(|ledger| ledger.sign_message(&self.derivation_path, message))
             .map_err(ledger_to_signer_error)?
             .as_ref()
             .try_into()
@@ -70,10 +429,355 @@ fn ledger_to_signer_error(e: RemoteWalletError) -> SignerError {
     SignerError::Custom(e.to_string())
 }
 
+fn use_color(cli: &Cli) -> bool {
+    !cli.no_color
+        && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        && std::io::stdout().is_terminal()
+}
+
+fn scan_accounts(ledger: &LedgerWallet, count: u32) -> anyhow::Result<()> {
+    for account in 0..count {
+        let pubkey = ledger.get_pubkey(&DerivationPath::new_bip44(Some(account), None), false)?;
+        println!("Account #{}: {}", account, pubkey);
+    }
+    Ok(())
+}
+
+/// Reads a signing request, checking that it is consistent and expects a
+/// signature of `signer`, and returns its message.
+fn read_request(path: &Path, signer: &Pubkey) -> anyhow::Result<Vec<u8>> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        anyhow::Error::msg(format!("failed to read request {}: {}", path.display(), e))
+    })?;
+    let request: SigningRequest = serde_json::from_str(&content)?;
+    if request.version != SIGNING_REQUEST_VERSION {
+        return Err(anyhow::Error::msg(format!(
+            "unsupported signing request version {}",
+            request.version
+        )));
+    }
+    let data = BASE64_STANDARD.decode(&request.message)?;
+    if hash(&data).to_string() != request.message_hash {
+        return Err(anyhow::Error::msg(
+            "the message doesn't match the hash of the request",
+        ));
+    }
+    let message: Message = bincode::deserialize(&data)?;
+    message
+        .sanitize()
+        .map_err(|e| anyhow::Error::msg(format!("malformed message: {}", e)))?;
+    let required = &message.account_keys[..message.header.num_required_signatures as usize];
+    if required.len() != request.signers.len()
+        || required
+            .iter()
+            .zip(&request.signers)
+            .any(|(key, listed)| key.to_string() != listed.pubkey)
+    {
+        return Err(anyhow::Error::msg(
+            "the signers listed by the request don't match the message",
+        ));
+    }
+    if !required.contains(signer) {
+        return Err(anyhow::Error::msg(format!(
+            "{} is not a signer of the request",
+            signer
+        )));
+    }
+
+    print_message(&message);
+    Ok(data)
+}
+
+/// Durable nonce account and authority of a message advancing a nonce in its
+/// first instruction.
+fn durable_nonce(message: &Message) -> Option<(Pubkey, Pubkey)> {
+    let ix = message.instructions.first()?;
+    if message.account_keys[ix.program_id_index as usize] != solana_sdk::system_program::ID {
+        return None;
+    }
+    match bincode::deserialize(&ix.data) {
+        Ok(SystemInstruction::AdvanceNonceAccount) => {}
+        _ => return None,
+    }
+    // AdvanceNonceAccount takes the nonce account, the recent blockhashes
+    // sysvar and the authority.
+    let key = |position: usize| Some(message.account_keys[*ix.accounts.get(position)? as usize]);
+    Some((key(0)?, key(2)?))
+}
+
+/// Instruction proposed by the data of a multisig `create_transaction`:
+/// its program, the number of its accounts and its data.
+fn proposed_instruction(args: &[u8]) -> Option<(Pubkey, usize, &[u8])> {
+    let program_id = Pubkey::try_from(args.get(..32)?).ok()?;
+    let accounts = u32::from_le_bytes(args.get(32..36)?.try_into().ok()?) as usize;
+    // Each account is a pubkey and the signer and writable flags.
+    let data_start = 36usize.checked_add(accounts.checked_mul(34)?)?;
+    let len = u32::from_le_bytes(args.get(data_start..data_start + 4)?.try_into().ok()?) as usize;
+    let data = args.get(data_start + 4..data_start + 4 + len)?;
+    Some((program_id, accounts, data))
+}
+
+/// Describes an instruction from its bytes: system instructions and token
+/// transfers in full, multisig instructions by name along with the
+/// instruction they propose, and the others by their program.
+fn describe_instruction(program_id: &Pubkey, data: &[u8]) -> String {
+    if *program_id == solana_sdk::system_program::ID {
+        return match bincode::deserialize::<SystemInstruction>(data) {
+            Ok(ix) => format!("system: {:?}", ix),
+            Err(_) => "system: unknown instruction".to_string(),
+        };
+    }
+    let name = KNOWN_PROGRAMS
+        .iter()
+        .find(|(id, _)| id == program_id)
+        .map(|(_, name)| *name);
+    let amount =
+        |range: std::ops::Range<usize>| Some(u64::from_le_bytes(data.get(range)?.try_into().ok()?));
+    match (name, data) {
+        (Some(name @ ("token" | "token-2022")), [3, ..]) => {
+            if let Some(amount) = amount(1..9) {
+                return format!("{}: Transfer of {} base units", name, amount);
+            }
+        }
+        (Some(name @ ("token" | "token-2022")), [12, ..]) => {
+            if let (Some(amount), Some(decimals)) = (amount(1..9), data.get(9)) {
+                return format!(
+                    "{}: TransferChecked of {} base units, {} decimals",
+                    name, amount, decimals
+                );
+            }
+        }
+        (Some(name), _) => return format!("{}: {} bytes of data", name, data.len()),
+        (None, _) => {}
+    }
+    let multisig_instruction = serum_common::multisig::INSTRUCTIONS
+        .into_iter()
+        .find(|name| {
+            data.starts_with(&hash(format!("global:{}", name).as_bytes()).to_bytes()[..8])
+        });
+    match multisig_instruction {
+        Some("create_transaction") => match proposed_instruction(&data[8..]) {
+            Some((proposed_program, accounts, proposed_data)) => format!(
+                "program {}: multisig create_transaction proposing, with {} accounts, {}",
+                program_id,
+                accounts,
+                describe_instruction(&proposed_program, proposed_data)
+            ),
+            None => format!(
+                "program {}: multisig create_transaction of a malformed instruction",
+                program_id
+            ),
+        },
+        Some(instruction) => format!("program {}: multisig {}", program_id, instruction),
+        None => format!("program {}: {} bytes of data", program_id, data.len()),
+    }
+}
+
+/// Prints the instructions and signers of a sanitized message.
+fn print_message(message: &Message) {
+    println!("Instructions, decoded from the message:");
+    for ix in &message.instructions {
+        println!(
+            "  {}",
+            describe_instruction(
+                &message.account_keys[ix.program_id_index as usize],
+                &ix.data
+            )
+        );
+    }
+    let nonce = durable_nonce(message);
+    println!("Signers:");
+    for (i, key) in message.account_keys[..message.header.num_required_signatures as usize]
+        .iter()
+        .enumerate()
+    {
+        let mut roles = vec!["signer"];
+        if i == 0 {
+            roles.push("fee payer");
+        }
+        if nonce.is_some_and(|(_, authority)| authority == *key) {
+            roles.push("nonce authority");
+        }
+        println!("  {} ({})", key, roles.join(", "));
+    }
+    match nonce {
+        Some((account, authority)) => println!(
+            "Durable nonce: {} of account {}, authority {}",
+            message.recent_blockhash, account, authority
+        ),
+        None => println!("Recent blockhash: {}", message.recent_blockhash),
+    }
+}
+
+/// Owners of a multisig account: after the account discriminator, the owners
+/// come first as a length-prefixed vector. `None` for another account.
+fn multisig_owners(account: &Account, pid: &Pubkey) -> Option<Vec<Pubkey>> {
+    if account.owner.to_bytes() != pid.to_bytes() {
+        return None;
+    }
+    let data = &account.data;
+    // Anchor discriminator of the account type.
+    let discriminator = &hash(b"account:Multisig").to_bytes()[..8];
+    if !data.starts_with(discriminator) {
+        return None;
+    }
+    let len = u32::from_le_bytes(data.get(8..12)?.try_into().ok()?) as usize;
+    data.get(12..12 + 32 * len)?
+        .chunks(32)
+        .map(|key| Pubkey::try_from(key).ok())
+        .collect()
+}
+
+fn whoami(signer: impl Signer, cli: &Cli) -> anyhow::Result<()> {
+    let pubkey = signer.try_pubkey()?;
+    let styled = if use_color(cli) {
+        style(pubkey).green()
+    } else {
+        style(pubkey)
+    };
+    println!("Pubkey: {}", styled);
+    if cli.offline {
+        return Ok(());
+    }
+    let rpc = RpcClient::new(cli.cluster.url().to_string());
+    let balance = rpc.get_balance(&RpcPubkey::new_from_array(pubkey.to_bytes()))?;
+    println!(
+        "Balance on {}: {} SOL",
+        cli.cluster,
+        lamports_to_sol(balance)
+    );
+    if let Some(multisig) = cli.multisig {
+        let pid = match cli.pid {
+            Some(pid) => pid,
+            None => DEFAULT_PID.parse()?,
+        };
+        let account = rpc.get_account(&RpcPubkey::new_from_array(multisig.to_bytes()))?;
+        let owners = multisig_owners(&account, &pid).ok_or_else(|| {
+            anyhow::Error::msg(format!(
+                "{} is not a multisig account of program {}",
+                multisig, pid
+            ))
+        })?;
+        match owners.iter().position(|owner| *owner == pubkey) {
+            Some(index) => println!("Owner #{} of multisig {}", index, multisig),
+            None => println!("Not an owner of multisig {}", multisig),
+        }
+    }
+    Ok(())
+}
+
+/// Decodes `data` as a transaction with a slot for each signature, rather
+/// than as a bare message.
+fn as_transaction(data: &[u8]) -> Option<Transaction> {
+    let tx: Transaction = bincode::deserialize(data).ok()?;
+    let complete = tx.signatures.len() == tx.message.header.num_required_signatures as usize
+        && bincode::serialized_size(&tx).ok()? == data.len() as u64;
+    complete.then_some(tx)
+}
+
+/// Whether `data` is exactly one message.
+fn is_message(data: &[u8]) -> bool {
+    bincode::deserialize::<Message>(data)
+        .is_ok_and(|message| message.serialize().len() == data.len())
+}
+
+/// Decodes the input as a message, or as a transaction returned along with
+/// its message. Base58 strings are also valid base64 ones, so without an
+/// explicit encoding the input has to decode to a message or transaction in
+/// exactly one of them.
+fn decode_input(
+    input: &str,
+    encoding: Option<Encoding>,
+) -> anyhow::Result<(Vec<u8>, Option<Transaction>)> {
+    let input = input.trim();
+    let encodings = match encoding {
+        Some(encoding) => vec![encoding],
+        None => vec![Encoding::Base58, Encoding::Base64],
+    };
+    let mut decoded: Vec<(Vec<u8>, Option<Transaction>)> = encodings
+        .into_iter()
+        .filter_map(|encoding| encoding.decode(input))
+        .filter_map(|data| match as_transaction(&data) {
+            Some(tx) => Some((tx.message_data(), Some(tx))),
+            None => is_message(&data).then_some((data, None)),
+        })
+        .collect();
+    match decoded.len() {
+        1 => Ok(decoded.remove(0)),
+        0 => Err(anyhow::Error::msg(
+            "the input is not a message or transaction, check --encoding",
+        )),
+        _ => Err(anyhow::Error::msg(
+            "the input decodes both as base58 and as base64, pass --encoding",
+        )),
+    }
+}
+
 fn run(signer: impl Signer, cli: Cli) -> anyhow::Result<()> {
-    let message = BASE64_STANDARD.decode(cli.transaction)?;
-    let sig = signer.sign_message(&message);
-    println!("Message signed: {}", style(sig).green());
+    let mut transaction = None;
+    let sig = if cli.offchain {
+        let text = cli
+            .transaction
+            .as_deref()
+            .expect("clap requires the transaction");
+        if cli.domain.is_none() {
+            offchain::check_domainless(text)?;
+        }
+        let text = offchain_text(cli.domain.as_deref(), text);
+        OffchainMessage::new(0, text.as_bytes())?.sign(&signer)?
+    } else {
+        let pubkey = signer.try_pubkey()?;
+        let message = match (&cli.request, &cli.transaction) {
+            (Some(path), _) => read_request(path, &pubkey)?,
+            (None, Some(input)) => {
+                let (message, tx) = decode_input(input, cli.encoding)?;
+                transaction = tx;
+                message
+            }
+            (None, None) => unreachable!("clap requires the transaction or the request"),
+        };
+        let parsed: Message = bincode::deserialize(&message)?;
+        parsed
+            .sanitize()
+            .map_err(|e| anyhow::Error::msg(format!("malformed message: {}", e)))?;
+        // Checked before signing, the Ledger may ask for a confirmation.
+        let slot = parsed.account_keys[..parsed.header.num_required_signatures as usize]
+            .iter()
+            .position(|key| *key == pubkey)
+            .ok_or_else(|| {
+                anyhow::Error::msg(format!("{} is not a signer of the message", pubkey))
+            })?;
+        if cli.print_message_hash {
+            println!("Message hash (SHA-256): {}", hash(&message));
+        }
+        if cli.assemble && transaction.is_none() {
+            transaction = Some(Transaction::new_unsigned(parsed));
+        }
+        let sig = signer.sign_message(&message);
+        if let Some(tx) = &mut transaction {
+            tx.signatures[slot] = sig;
+        }
+        sig
+    };
+    let styled = if use_color(&cli) {
+        style(sig).green()
+    } else {
+        style(sig)
+    };
+    println!("Message signed: {}", styled);
+    if let Some(tx) = transaction {
+        let missing = tx
+            .signatures
+            .iter()
+            .filter(|sig| **sig == Signature::default())
+            .count();
+        println!(
+            "Transaction with {} of {} signatures: {}",
+            tx.signatures.len() - missing,
+            tx.signatures.len(),
+            BASE64_STANDARD.encode(bincode::serialize(&tx)?)
+        );
+    }
     Ok(())
 }
 
@@ -82,30 +786,38 @@ fn main() -> anyhow::Result<()> {
 
     if cli.ledger {
         let wallet_manager = solana_remote_wallet::remote_wallet::initialize_wallet_manager()?;
-        wallet_manager.update_devices()?;
-        let ledger_info = wallet_manager
-            .list_devices()
-            .into_iter()
-            .find(|wi| matches!(wi.manufacturer, Manufacturer::Ledger))
-            .ok_or(anyhow::Error::msg("Ledger not found. Please, ensure that it is connected, unlocked, and the Solana app is opened"))?;
-        let ledger = wallet_manager.get_ledger(&ledger_info.host_device_path)?;
+        let ledger = connect_ledger(&wallet_manager, cli.ledger_retries).map_err(|e| match e {
+            RemoteWalletError::NoDeviceFound => anyhow::Error::msg("Ledger not found. Please, ensure that it is connected, unlocked, and the Solana app is opened"),
+            e => e.into(),
+        })?;
+        if let Some(count) = cli.scan_accounts {
+            return scan_accounts(&ledger, count);
+        }
         let signer = LedgerSigner {
-            ledger,
+            ledger: RefCell::new(ledger),
+            wallet_manager,
+            retries: cli.ledger_retries,
             derivation_path: DerivationPath::new_bip44(cli.account_number, None),
+            confirm_timeout: cli.ledger_confirm_timeout.map(Duration::from_secs),
         };
+        if cli.whoami {
+            return whoami(signer, &cli);
+        }
         run(signer, cli)?;
     } else {
-        run(
-            Rc::new(
-                Keypair::read_from_file(
-                    cli.key_file
-                        .clone()
-                        .ok_or(anyhow::Error::msg("private-key is required"))?,
-                )
-                .unwrap(),
-            ),
-            cli,
-        )?;
+        let key_file = cli
+            .key_file
+            .clone()
+            .ok_or(anyhow::Error::msg("private-key is required"))?;
+        let signer = Rc::new(serum_common::keypair::read_keypair(
+            &key_file,
+            cli.keypair_format,
+            Keypair::from_bytes,
+        )?);
+        if cli.whoami {
+            return whoami(signer, &cli);
+        }
+        run(signer, cli)?;
     };
     Ok(())
 }
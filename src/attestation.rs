@@ -0,0 +1,167 @@
+//! Proposal attestations: the decoded contents of a transaction account with
+//! an off-chain message signed by its proposer, stating that they created and
+//! reviewed it, for the approvers to check its provenance.
+
+use anchor_client::{
+    anchor_lang::solana_program::hash,
+    solana_sdk::{offchain_message::OffchainMessage, pubkey::Pubkey, signature::Signature},
+};
+use base64::{engine::Engine, prelude::BASE64_STANDARD};
+use serde::{Deserialize, Serialize};
+
+use crate::decode;
+use crate::error::SerumError;
+
+/// Version 2 signs the description along with the instruction.
+pub const VERSION: u32 = 2;
+
+const STATEMENT: &str = "I created and reviewed this proposal";
+
+#[derive(Serialize, Deserialize)]
+pub struct Attestation {
+    version: u32,
+    pub transaction: String,
+    multisig: String,
+    program_id: String,
+    /// Instruction accounts, as `PUBKEY`, `PUBKEY:s`, `PUBKEY:w` or `PUBKEY:sw`.
+    accounts: Vec<String>,
+    /// Base64 instruction data.
+    data: String,
+    description: String,
+    pub proposer: String,
+    /// Base58 off-chain signature of the statement.
+    signature: String,
+}
+
+fn account_label(account: &coral_multisig::TransactionAccount) -> String {
+    let flags = match (account.is_signer, account.is_writable) {
+        (false, false) => "",
+        (true, false) => ":s",
+        (false, true) => ":w",
+        (true, true) => ":sw",
+    };
+    format!("{}{}", account.pubkey, flags)
+}
+
+/// Statement the proposer signs, binding the transaction account to the
+/// hash of the instruction it holds and to its description.
+fn statement(
+    transaction: &str,
+    multisig: &str,
+    program_id: &str,
+    accounts: &[String],
+    data: &str,
+    description: &str,
+) -> String {
+    let instruction = format!("{}\n{}\n{}", program_id, accounts.join("\n"), data);
+    format!(
+        "{}: transaction {} of multisig {}, instruction SHA-256 {}, described as: {}",
+        STATEMENT,
+        transaction,
+        multisig,
+        hash::hash(instruction.as_bytes()),
+        description
+    )
+}
+
+impl Attestation {
+    /// Attests `transaction_account`, signing with `sign`, which signs an
+    /// off-chain message as the proposer.
+    pub fn new(
+        transaction: &Pubkey,
+        transaction_account: &coral_multisig::Transaction,
+        multisig_program: &Pubkey,
+        proposer: &Pubkey,
+        sign: impl FnOnce(&OffchainMessage) -> anyhow::Result<Signature>,
+    ) -> anyhow::Result<Self> {
+        let accounts: Vec<_> = transaction_account
+            .accounts
+            .iter()
+            .map(account_label)
+            .collect();
+        let data = BASE64_STANDARD.encode(&transaction_account.data);
+        let (transaction, multisig, program_id) = (
+            transaction.to_string(),
+            transaction_account.multisig.to_string(),
+            transaction_account.program_id.to_string(),
+        );
+        let description = decode::describe_instruction(
+            &transaction_account.program_id,
+            multisig_program,
+            &transaction_account.data,
+        );
+        let message = OffchainMessage::new(
+            0,
+            statement(
+                &transaction,
+                &multisig,
+                &program_id,
+                &accounts,
+                &data,
+                &description,
+            )
+            .as_bytes(),
+        )?;
+        Ok(Self {
+            version: VERSION,
+            signature: sign(&message)?.to_string(),
+            description,
+            transaction,
+            multisig,
+            program_id,
+            accounts,
+            data,
+            proposer: proposer.to_string(),
+        })
+    }
+
+    /// File name identifying the attestation by its transaction.
+    pub fn file_name(&self) -> String {
+        format!("attestation-{}.json", self.transaction)
+    }
+
+    /// Checks the signature of the proposer over the attested contents.
+    pub fn verify(&self) -> anyhow::Result<()> {
+        if self.version != VERSION {
+            return Err(SerumError::Validation(format!(
+                "unsupported attestation version {}",
+                self.version
+            ))
+            .into());
+        }
+        let message = OffchainMessage::new(
+            0,
+            statement(
+                &self.transaction,
+                &self.multisig,
+                &self.program_id,
+                &self.accounts,
+                &self.data,
+                &self.description,
+            )
+            .as_bytes(),
+        )?;
+        let signature: Signature = self.signature.parse()?;
+        if !message.verify(&self.proposer.parse()?, &signature)? {
+            return Err(SerumError::Validation(format!(
+                "the attestation isn't signed by its proposer {}",
+                self.proposer
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Whether the attested contents are those of `transaction_account`.
+    pub fn matches(&self, transaction_account: &coral_multisig::Transaction) -> bool {
+        self.multisig == transaction_account.multisig.to_string()
+            && self.program_id == transaction_account.program_id.to_string()
+            && self.data == BASE64_STANDARD.encode(&transaction_account.data)
+            && self.accounts
+                == transaction_account
+                    .accounts
+                    .iter()
+                    .map(account_label)
+                    .collect::<Vec<_>>()
+    }
+}
@@ -0,0 +1,117 @@
+//! Submission of transaction bundles to a block engine, which lands them
+//! atomically in the given order or not at all.
+//!
+//! Block engines only accept bundles paying them a tip, a transfer to one of
+//! their tip accounts in one of the transactions.
+
+use anchor_client::solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    system_instruction::{self, SystemInstruction},
+    system_program,
+    transaction::Transaction,
+};
+use serde_json::{json, Value};
+
+use crate::error::SerumError;
+
+/// Bundle endpoint of the Jito mainnet block engine.
+pub const MAINNET_ENDPOINT: &str = "https://mainnet.block-engine.jito.wtf/api/v1/bundles";
+
+/// Tip accounts of the Jito block engine.
+const TIP_ACCOUNTS: [&str; 8] = [
+    "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5",
+    "HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe",
+    "Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY",
+    "ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49",
+    "DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh",
+    "ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt",
+    "DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL",
+    "3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT",
+];
+
+fn tip_accounts() -> impl Iterator<Item = Pubkey> {
+    TIP_ACCOUNTS
+        .iter()
+        .map(|account| account.parse().expect("valid tip account"))
+}
+
+/// Endpoint bundles are sent to when none is given. Only mainnet has a
+/// well-known block engine.
+pub fn default_endpoint(mainnet: bool) -> Option<&'static str> {
+    mainnet.then_some(MAINNET_ENDPOINT)
+}
+
+/// Transfer of `lamports` from `payer` to a tip account, picked from the
+/// payer so that its bundles don't all contend for the same account.
+pub fn tip_instruction(payer: &Pubkey, lamports: u64) -> Instruction {
+    let index = payer.to_bytes()[0] as usize % TIP_ACCOUNTS.len();
+    let tip_account = tip_accounts()
+        .nth(index)
+        .expect("index within the tip accounts");
+    system_instruction::transfer(payer, &tip_account, lamports)
+}
+
+/// Refuses a bundle none of whose transactions tips the block engine, which
+/// would reject it.
+pub fn check_tip(transactions: &[Transaction]) -> anyhow::Result<()> {
+    let tip_accounts: Vec<Pubkey> = tip_accounts().collect();
+    let tips = transactions.iter().any(|tx| {
+        let keys = &tx.message.account_keys;
+        tx.message.instructions.iter().any(|ix| {
+            keys.get(ix.program_id_index as usize) == Some(&system_program::id())
+                && matches!(
+                    bincode::deserialize(&ix.data),
+                    Ok(SystemInstruction::Transfer { lamports }) if lamports > 0
+                )
+                && ix
+                    .accounts
+                    .get(1)
+                    .and_then(|&index| keys.get(index as usize))
+                    .is_some_and(|to| tip_accounts.contains(to))
+        })
+    });
+    if !tips {
+        return Err(SerumError::Validation(
+            "no transaction of the bundle tips the block engine, which rejects such bundles; build it with ApproveAndExecute --as-bundle --tip".to_string(),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Sends the transactions with `sendBundle`, returning the bundle id.
+pub async fn send_bundle(endpoint: &str, transactions: &[Transaction]) -> anyhow::Result<String> {
+    let encoded = transactions
+        .iter()
+        .map(|tx| Ok(bs58::encode(bincode::serialize(tx)?).into_string()))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "sendBundle",
+        "params": [encoded],
+    });
+    log::info!(
+        "sending bundle of {} transactions to {}",
+        transactions.len(),
+        endpoint
+    );
+    let response: Value = reqwest::Client::new()
+        .post(endpoint)
+        .json(&request)
+        .send()
+        .await?
+        .json()
+        .await?;
+    if let Some(error) = response.get("error") {
+        return Err(anyhow::Error::msg(format!(
+            "block engine rejected the bundle: {}",
+            error
+        )));
+    }
+    response["result"]
+        .as_str()
+        .map(ToString::to_string)
+        .ok_or_else(|| anyhow::Error::msg(format!("unexpected response: {}", response)))
+}
@@ -0,0 +1,64 @@
+//! Terminal styling that can be switched off for the whole invocation.
+
+use std::fmt::{self, Display};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crossterm::style::{ContentStyle, StyledContent, Stylize};
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether styling should be on by default: honors `NO_COLOR` and disables
+/// styling when stdout is not a terminal.
+pub fn enabled_by_default() -> bool {
+    use std::io::IsTerminal;
+
+    std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()) && std::io::stdout().is_terminal()
+}
+
+/// Content styled with the usual `Stylize` methods, printed plain when
+/// styling is disabled.
+pub struct Styled<D> {
+    content: D,
+    style: ContentStyle,
+}
+
+pub fn style<D: Display>(content: D) -> Styled<D> {
+    Styled {
+        content,
+        style: ContentStyle::new(),
+    }
+}
+
+impl<D> AsRef<ContentStyle> for Styled<D> {
+    fn as_ref(&self) -> &ContentStyle {
+        &self.style
+    }
+}
+
+impl<D> AsMut<ContentStyle> for Styled<D> {
+    fn as_mut(&mut self) -> &mut ContentStyle {
+        &mut self.style
+    }
+}
+
+impl<D: Display> Stylize for Styled<D> {
+    type Styled = Self;
+
+    fn stylize(self) -> Self {
+        self
+    }
+}
+
+impl<D: Display> Display for Styled<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if ENABLED.load(Ordering::Relaxed) {
+            StyledContent::new(self.style, &self.content).fmt(f)
+        } else {
+            self.content.fmt(f)
+        }
+    }
+}
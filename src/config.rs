@@ -0,0 +1,144 @@
+//! Named profiles read from a TOML config file, e.g.
+//!
+//! ```toml
+//! [profile.devnet]
+//! pid = "AAHT26ecV3FEeFmL2gDZW6FfEqjPkghHbAkNZGqwT8Ww"
+//! cluster = "devnet"
+//! private-key = "/home/alice/keys/devnet.json"
+//!
+//! [profile.mainnet]
+//! pid = "msigUdDBsR4zSUYqYEDrc1LcgtmuSDDM7KxpRUXNC6U"
+//! cluster = "https://my-rpc.example.com"
+//! ws-url = "wss://my-rpc.example.com"
+//! ```
+//!
+//! and the registry of known multisigs.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anchor_client::{solana_sdk::pubkey::Pubkey, Cluster};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    profile: BTreeMap<String, Profile>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Profile {
+    pid: Option<String>,
+    cluster: Option<String>,
+    ws_url: Option<String>,
+    private_key: Option<String>,
+}
+
+impl Config {
+    pub fn default_path() -> Option<PathBuf> {
+        std::env::var_os("HOME").map(|home| Path::new(&home).join(".config/serum/config.toml"))
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            anyhow::Error::msg(format!("failed to read config {}: {}", path.display(), e))
+        })?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    pub fn take_profile(mut self, name: &str) -> anyhow::Result<Profile> {
+        self.profile.remove(name).ok_or_else(|| {
+            anyhow::Error::msg(format!(
+                "profile {} not found, available profiles: {}",
+                name,
+                self.profile.keys().cloned().collect::<Vec<_>>().join(", ")
+            ))
+        })
+    }
+}
+
+/// Registry of known multisigs, a JSON file mapping each multisig address to
+/// the program owning it and the cluster it lives on, e.g.
+///
+/// ```json
+/// {
+///   "5bqMXzqYp3GjjsnFvXXGhSTKLLGgRy5URhkqcAdKnswA": {
+///     "pid": "msigUdDBsR4zSUYqYEDrc1LcgtmuSDDM7KxpRUXNC6U",
+///     "cluster": "mainnet"
+///   }
+/// }
+/// ```
+#[derive(Deserialize)]
+pub struct Registry(BTreeMap<String, RegistryEntry>);
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegistryEntry {
+    pid: String,
+    cluster: Option<String>,
+}
+
+impl Registry {
+    pub fn default_path() -> Option<PathBuf> {
+        std::env::var_os("HOME").map(|home| Path::new(&home).join(".config/serum/registry.json"))
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            anyhow::Error::msg(format!("failed to read registry {}: {}", path.display(), e))
+        })?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn get(&self, multisig: &Pubkey) -> Option<&RegistryEntry> {
+        self.0.get(&multisig.to_string())
+    }
+}
+
+impl RegistryEntry {
+    pub fn pid(&self) -> anyhow::Result<Pubkey> {
+        Ok(self.pid.parse()?)
+    }
+
+    pub fn cluster(&self) -> anyhow::Result<Option<Cluster>> {
+        self.cluster.as_deref().map(parse_cluster).transpose()
+    }
+}
+
+/// Parses a cluster like `Cluster::from_str`, also accepting `localhost` for
+/// the default ports of `solana-test-validator`.
+pub fn parse_cluster(s: &str) -> anyhow::Result<Cluster> {
+    match s {
+        "localhost" => Ok(Cluster::Custom(
+            "http://127.0.0.1:8899".to_string(),
+            "ws://127.0.0.1:8900".to_string(),
+        )),
+        _ => s.parse(),
+    }
+}
+
+impl Profile {
+    pub fn pid(&self) -> anyhow::Result<Option<Pubkey>> {
+        Ok(self.pid.as_deref().map(str::parse).transpose()?)
+    }
+
+    pub fn cluster(&self) -> anyhow::Result<Option<Cluster>> {
+        let Some(cluster) = &self.cluster else {
+            return Ok(None);
+        };
+        match (parse_cluster(cluster)?, &self.ws_url) {
+            (Cluster::Custom(url, _), Some(ws_url)) => {
+                Ok(Some(Cluster::Custom(url, ws_url.clone())))
+            }
+            (_, Some(_)) => Err(anyhow::Error::msg(
+                "ws-url can only be set together with a cluster URL",
+            )),
+            (cluster, None) => Ok(Some(cluster)),
+        }
+    }
+
+    pub fn private_key(&self) -> Option<String> {
+        self.private_key.clone()
+    }
+}
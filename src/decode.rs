@@ -0,0 +1,509 @@
+//! Human readable descriptions of transaction messages.
+
+use anchor_client::{
+    anchor_lang::{solana_program::hash, AnchorDeserialize},
+    solana_sdk::{
+        message::Message, native_token::lamports_to_sol, pubkey::Pubkey,
+        system_instruction::SystemInstruction, system_program,
+    },
+};
+use crossterm::style::Stylize;
+use serde_json::{json, Value};
+use spl_token::instruction::TokenInstruction;
+use spl_token_2022::{
+    extension::transfer_fee::instruction::TransferFeeInstruction,
+    instruction::TokenInstruction as Token2022Instruction,
+};
+
+use crate::color::style;
+use crate::derive_multisig_signer;
+use crate::idl::Idl;
+
+/// Anchor discriminator of a global instruction: the first 8 bytes of the
+/// hash of `global:<name>`.
+pub fn sighash(name: &str) -> [u8; 8] {
+    let mut discriminator = [0; 8];
+    discriminator
+        .copy_from_slice(&hash::hash(format!("global:{}", name).as_bytes()).to_bytes()[..8]);
+    discriminator
+}
+
+pub fn multisig_instruction_name(data: &[u8]) -> Option<&'static str> {
+    serum_common::multisig::INSTRUCTIONS
+        .into_iter()
+        .find(|name| data.starts_with(&sighash(name)))
+}
+
+/// Formats a token amount in UI units followed by the exact on-chain value.
+pub fn format_amount(amount: u64, decimals: u8) -> String {
+    format!(
+        "{} ({} base units)",
+        spl_token::amount_to_ui_amount_string_trimmed(amount, decimals),
+        amount
+    )
+}
+
+pub fn format_lamports(lamports: u64) -> String {
+    format!("{} SOL ({} lamports)", lamports_to_sol(lamports), lamports)
+}
+
+/// Describes an instruction of the programs this tool builds transactions for.
+pub fn describe_instruction(program_id: &Pubkey, multisig_program: &Pubkey, data: &[u8]) -> String {
+    if system_program::check_id(program_id) {
+        match bincode::deserialize::<SystemInstruction>(data) {
+            Ok(ix) => format!("system: {:?}", ix),
+            Err(_) => "system: unknown instruction".to_string(),
+        }
+    } else if program_id.to_bytes() == spl_token::id().to_bytes() {
+        match TokenInstruction::unpack(data) {
+            Ok(TokenInstruction::Transfer { amount }) => {
+                format!("token: Transfer of {} base units", amount)
+            }
+            Ok(TokenInstruction::TransferChecked { amount, decimals }) => {
+                format!(
+                    "token: TransferChecked of {}",
+                    format_amount(amount, decimals)
+                )
+            }
+            Ok(TokenInstruction::Approve { amount }) => {
+                format!("token: Approve of a delegate for {} base units", amount)
+            }
+            Ok(TokenInstruction::Revoke) => "token: Revoke of the delegate".to_string(),
+            Ok(ix) => format!("token: {:?}", ix),
+            Err(_) => "token: unknown instruction".to_string(),
+        }
+    } else if *program_id == spl_token_2022::id() {
+        match Token2022Instruction::unpack(data) {
+            Ok(Token2022Instruction::TransferFeeExtension(
+                TransferFeeInstruction::TransferCheckedWithFee {
+                    amount,
+                    decimals,
+                    fee,
+                },
+            )) => format!(
+                "token-2022: TransferCheckedWithFee of {}, withholding a fee of {}",
+                format_amount(amount, decimals),
+                format_amount(fee, decimals)
+            ),
+            Ok(ix) => format!("token-2022: {:?}", ix),
+            Err(_) => "token-2022: unknown instruction".to_string(),
+        }
+    } else if program_id == multisig_program {
+        match multisig_instruction_name(data) {
+            Some(name) => format!("multisig: {}", name),
+            None => "multisig: unknown instruction".to_string(),
+        }
+    } else {
+        format!("unknown program, {} bytes of data", data.len())
+    }
+}
+
+/// Roles of the accounts of a token instruction, in the order it takes them.
+pub fn account_roles(program_id: &Pubkey, data: &[u8]) -> &'static [&'static str] {
+    let is_token = program_id.to_bytes() == spl_token::id().to_bytes();
+    if !is_token && *program_id != spl_token_2022::id() {
+        return &[];
+    }
+    // Token-2022 keeps the tags and account layouts of the Token instructions.
+    match data {
+        [1, ..] => &["account", "mint", "owner", "rent sysvar"],
+        [3, ..] => &["source", "destination", "authority"],
+        [4, ..] => &["source", "delegate", "owner"],
+        [5, ..] => &["source", "owner"],
+        [6, ..] => &["account or mint", "current authority"],
+        [7, ..] | [14, ..] => &["mint", "destination", "mint authority"],
+        [8, ..] | [15, ..] => &["account", "mint", "authority"],
+        [9, ..] => &["account", "destination", "owner"],
+        [10, ..] | [11, ..] => &["account", "mint", "freeze authority"],
+        [12, ..] => &["source", "mint", "destination", "authority"],
+        [13, ..] => &["source", "mint", "delegate", "owner"],
+        [17, ..] => &["account"],
+        // TransferCheckedWithFee of the transfer fee extension.
+        [26, 1, ..] if !is_token => &["source", "mint", "destination", "authority"],
+        _ => &[],
+    }
+}
+
+/// Suffix naming the role of the `position`-th account, if known.
+pub fn role_label(roles: &[&str], position: usize) -> String {
+    roles
+        .get(position)
+        .map_or(String::new(), |role| format!(" ({})", role))
+}
+
+/// What the dangerous instruction heuristics know about the multisig.
+#[derive(Default)]
+pub struct DangerContext<'a> {
+    /// Signer of the multisig, the expected authority and destination.
+    pub multisig_signer: Option<Pubkey>,
+    /// Current owners, to measure owner changes against.
+    pub owners: Option<&'a [Pubkey]>,
+}
+
+impl DangerContext<'_> {
+    /// Whether `key` is known not to be the multisig signer.
+    fn is_foreign(&self, key: &[u8; 32]) -> bool {
+        self.multisig_signer
+            .is_some_and(|signer| signer.to_bytes() != *key)
+    }
+}
+
+/// Flags instructions that warrant extra scrutiny: authorities handed to
+/// other keys or removed, accounts closed to other addresses, and owner and
+/// threshold changes weakening the multisig.
+pub fn dangers(
+    program_id: &Pubkey,
+    multisig_program: &Pubkey,
+    accounts: &[Pubkey],
+    data: &[u8],
+    context: &DangerContext,
+) -> Vec<String> {
+    let mut dangers = Vec::new();
+    // Token-2022 keeps the tags and account layouts of the Token instructions.
+    let token = if program_id.to_bytes() == spl_token::id().to_bytes() {
+        TokenInstruction::unpack(data).ok().map(|ix| match ix {
+            TokenInstruction::SetAuthority {
+                authority_type,
+                new_authority,
+            } => Some((
+                format!("{:?}", authority_type),
+                Option::from(new_authority)
+                    .map(|key: spl_token::solana_program::pubkey::Pubkey| key.to_bytes()),
+            )),
+            _ => None,
+        })
+    } else if *program_id == spl_token_2022::id() {
+        Token2022Instruction::unpack(data).ok().map(|ix| match ix {
+            Token2022Instruction::SetAuthority {
+                authority_type,
+                new_authority,
+            } => Some((
+                format!("{:?}", authority_type),
+                Option::from(new_authority).map(|key: Pubkey| key.to_bytes()),
+            )),
+            _ => None,
+        })
+    } else {
+        None
+    };
+    if let Some(set_authority) = token {
+        match set_authority {
+            Some((authority_type, None)) => dangers.push(format!(
+                "SetAuthority removes the {} authority for good",
+                authority_type
+            )),
+            Some((authority_type, Some(key))) if context.is_foreign(&key) => dangers.push(format!(
+                "SetAuthority hands the {} authority to {}, not the multisig signer",
+                authority_type,
+                Pubkey::from(key)
+            )),
+            _ => {}
+        }
+        if let ([9, ..], Some(destination)) = (data, accounts.get(1)) {
+            if context.is_foreign(&destination.to_bytes()) {
+                dangers.push(format!(
+                    "CloseAccount sends the balance of {} to {}, not the multisig signer",
+                    accounts[0], destination
+                ));
+            }
+        }
+    }
+
+    if program_id == multisig_program && data.len() >= 8 {
+        let mut args = &data[8..];
+        let (owners, threshold) = match multisig_instruction_name(data) {
+            Some("set_owners") => {
+                let owners = Vec::<Pubkey>::deserialize(&mut args).ok();
+                // The program lowers the threshold to the number of owners
+                // left, so a single owner can execute alone.
+                let threshold = owners
+                    .as_ref()
+                    .filter(|owners| owners.len() == 1)
+                    .map(|_| 1);
+                (owners, threshold)
+            }
+            // The program ignores the owners of this instruction.
+            Some("set_owners_and_change_threshold") => (
+                None,
+                Vec::<Pubkey>::deserialize(&mut args)
+                    .and_then(|_| u64::deserialize(&mut args))
+                    .ok(),
+            ),
+            Some("change_threshold") => (None, u64::deserialize(&mut args).ok()),
+            _ => (None, None),
+        };
+        if let (Some(owners), Some(current)) = (owners, context.owners) {
+            let removed = current
+                .iter()
+                .filter(|owner| !owners.contains(owner))
+                .count();
+            if removed > 0 && removed * 2 >= current.len() {
+                dangers.push(format!(
+                    "SetOwners removes {} of the {} owners",
+                    removed,
+                    current.len()
+                ));
+            }
+        }
+        if threshold == Some(1) {
+            dangers.push("the threshold drops to 1, any single owner can execute".to_string());
+        }
+    }
+    dangers
+}
+
+/// Program, accounts and data of the instruction the data of a multisig
+/// `create_transaction` instruction proposes.
+pub fn proposed_instruction(
+    data: &[u8],
+) -> Option<(Pubkey, Vec<coral_multisig::TransactionAccount>, Vec<u8>)> {
+    if multisig_instruction_name(data) != Some("create_transaction") {
+        return None;
+    }
+    let mut args = &data[8..];
+    let program_id = Pubkey::deserialize(&mut args).ok()?;
+    let accounts = Vec::<coral_multisig::TransactionAccount>::deserialize(&mut args).ok()?;
+    let data = Vec::<u8>::deserialize(&mut args).ok()?;
+    Some((program_id, accounts, data))
+}
+
+pub fn print_dangers(dangers: &[String]) {
+    for danger in dangers {
+        println!("  {}", style(format!("Danger: {}", danger)).red());
+    }
+}
+
+pub fn print_message(message: &Message, multisig_program: &Pubkey) {
+    let num_signers = message.header.num_required_signatures as usize;
+    println!("Fee payer: {}", style(message.account_keys[0]).green());
+    println!(
+        "Recent blockhash or nonce: {}",
+        style(message.recent_blockhash).green()
+    );
+    println!("Required signers:");
+    for key in &message.account_keys[..num_signers] {
+        println!("  {}", style(key).green());
+    }
+    for (i, ix) in message.instructions.iter().enumerate() {
+        let program_id = &message.account_keys[ix.program_id_index as usize];
+        println!(
+            "Instruction #{}: {}",
+            i,
+            style(describe_instruction(program_id, multisig_program, &ix.data)).bold()
+        );
+        println!("  Program: {}", program_id);
+        let roles = account_roles(program_id, &ix.data);
+        for (position, &index) in ix.accounts.iter().enumerate() {
+            println!(
+                "  Account: {}{}",
+                instruction_account(message, index),
+                role_label(roles, position)
+            );
+        }
+        let accounts: Vec<Pubkey> = ix
+            .accounts
+            .iter()
+            .map(|&index| message.account_keys[index as usize])
+            .collect();
+        print_dangers(&dangers(
+            program_id,
+            multisig_program,
+            &accounts,
+            &ix.data,
+            &DangerContext::default(),
+        ));
+        if program_id != multisig_program {
+            continue;
+        }
+        // The multisig a proposal is created for is its first account.
+        if let (Some((proposed_program, proposed_accounts, proposed_data)), Some(multisig)) =
+            (proposed_instruction(&ix.data), accounts.first())
+        {
+            println!(
+                "  Proposes: {}",
+                describe_instruction(&proposed_program, multisig_program, &proposed_data)
+            );
+            let context = DangerContext {
+                multisig_signer: Some(derive_multisig_signer(multisig, multisig_program).0),
+                owners: None,
+            };
+            let proposed_accounts: Vec<Pubkey> = proposed_accounts
+                .iter()
+                .map(|account| account.pubkey)
+                .collect();
+            let proposed = dangers(
+                &proposed_program,
+                multisig_program,
+                &proposed_accounts,
+                &proposed_data,
+                &context,
+            );
+            print_dangers(
+                &proposed
+                    .into_iter()
+                    .map(|danger| format!("the proposed instruction: {}", danger))
+                    .collect::<Vec<_>>(),
+            );
+        }
+    }
+}
+
+/// Prints every field of the message as serialized, without resolving the
+/// indices, for debugging serialization issues.
+pub fn dump_message_fields(message: &Message) {
+    let header = &message.header;
+    println!(
+        "header.num_required_signatures: {}",
+        header.num_required_signatures
+    );
+    println!(
+        "header.num_readonly_signed_accounts: {}",
+        header.num_readonly_signed_accounts
+    );
+    println!(
+        "header.num_readonly_unsigned_accounts: {}",
+        header.num_readonly_unsigned_accounts
+    );
+    println!("account_keys: {}", message.account_keys.len());
+    for (i, key) in message.account_keys.iter().enumerate() {
+        println!("  [{}] {}", i, key);
+    }
+    println!("recent_blockhash: {}", message.recent_blockhash);
+    println!("instructions: {}", message.instructions.len());
+    for (i, ix) in message.instructions.iter().enumerate() {
+        println!("  [{}] program_id_index: {}", i, ix.program_id_index);
+        println!("      accounts: {:?}", ix.accounts);
+        println!("      data ({} bytes): {}", ix.data.len(), hex(&ix.data));
+    }
+}
+
+/// Lists the instructions as JSON, with the arguments of the multisig
+/// instructions decoded by name with the program's IDL.
+pub fn instructions_json(
+    message: &Message,
+    multisig_program: &Pubkey,
+    idl: &Idl,
+) -> anyhow::Result<Value> {
+    message
+        .instructions
+        .iter()
+        .map(|ix| {
+            let program_id = &message.account_keys[ix.program_id_index as usize];
+            let accounts: Vec<_> = ix
+                .accounts
+                .iter()
+                .map(|&index| message.account_keys[index as usize].to_string())
+                .collect();
+            let decoded = if program_id == multisig_program {
+                idl.decode_instruction(&ix.data).transpose()?
+            } else {
+                None
+            };
+            Ok(match decoded {
+                Some((name, args)) => json!({
+                    "program": program_id.to_string(),
+                    "instruction": name,
+                    "args": args,
+                    "accounts": accounts,
+                }),
+                None => json!({
+                    "program": program_id.to_string(),
+                    "description": describe_instruction(program_id, multisig_program, &ix.data),
+                    "accounts": accounts,
+                }),
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map(Value::Array)
+}
+
+/// Account of a compiled instruction with its signer and writable flags.
+fn instruction_account(message: &Message, index: u8) -> String {
+    let index = index as usize;
+    let mut flags = Vec::new();
+    if message.is_signer(index) {
+        flags.push("signer");
+    }
+    if message.is_writable(index) {
+        flags.push("writable");
+    }
+    format!("{} {:?}", message.account_keys[index], flags)
+}
+
+fn print_change(field: &str, old: impl std::fmt::Display, new: impl std::fmt::Display) {
+    println!("{}:", field);
+    println!("  {}", style(format!("- {}", old)).red());
+    println!("  {}", style(format!("+ {}", new)).green());
+}
+
+/// Prints the differences between two messages field by field, returning
+/// whether there were any.
+pub fn compare_messages(old: &Message, new: &Message, multisig_program: &Pubkey) -> bool {
+    let mut changed = false;
+    let mut compare = |field: &str, old: String, new: String| {
+        if old != new {
+            print_change(field, old, new);
+            changed = true;
+        }
+    };
+    compare(
+        "Fee payer",
+        old.account_keys[0].to_string(),
+        new.account_keys[0].to_string(),
+    );
+    compare(
+        "Recent blockhash or nonce",
+        old.recent_blockhash.to_string(),
+        new.recent_blockhash.to_string(),
+    );
+    compare(
+        "Required signatures",
+        old.header.num_required_signatures.to_string(),
+        new.header.num_required_signatures.to_string(),
+    );
+    compare(
+        "Instructions",
+        old.instructions.len().to_string(),
+        new.instructions.len().to_string(),
+    );
+
+    for (i, (old_ix, new_ix)) in old.instructions.iter().zip(&new.instructions).enumerate() {
+        let old_program = &old.account_keys[old_ix.program_id_index as usize];
+        let new_program = &new.account_keys[new_ix.program_id_index as usize];
+        compare(
+            &format!("Instruction #{} program", i),
+            old_program.to_string(),
+            new_program.to_string(),
+        );
+        compare(
+            &format!("Instruction #{} data", i),
+            describe_instruction(old_program, multisig_program, &old_ix.data),
+            describe_instruction(new_program, multisig_program, &new_ix.data),
+        );
+        if old_ix.data != new_ix.data {
+            compare(
+                &format!("Instruction #{} raw data", i),
+                hex(&old_ix.data),
+                hex(&new_ix.data),
+            );
+        }
+        let accounts = old_ix.accounts.len().max(new_ix.accounts.len());
+        for j in 0..accounts {
+            let account = |message: &Message, ix: &[u8]| {
+                ix.get(j).map_or("none".to_string(), |&index| {
+                    instruction_account(message, index)
+                })
+            };
+            compare(
+                &format!("Instruction #{} account #{}", i, j),
+                account(old, &old_ix.accounts),
+                account(new, &new_ix.accounts),
+            );
+        }
+    }
+    changed
+}
+
+pub fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,50 @@
+//! Error categories scripts can tell apart by the exit status:
+//!
+//! | Status | Category                                                     |
+//! |--------|--------------------------------------------------------------|
+//! | 1      | any other error                                              |
+//! | 2      | invalid arguments or input, also used by the argument parser |
+//! | 3      | failed RPC request                                           |
+//! | 4      | insufficient funds                                           |
+//! | 5      | approval threshold not met                                   |
+
+use std::fmt;
+
+use anchor_client::solana_client::client_error::ClientError;
+
+#[derive(Debug)]
+pub enum SerumError {
+    /// Arguments or input rejected before anything was sent.
+    Validation(String),
+    InsufficientFunds(String),
+    ThresholdNotMet(String),
+}
+
+impl fmt::Display for SerumError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SerumError::Validation(message)
+            | SerumError::InsufficientFunds(message)
+            | SerumError::ThresholdNotMet(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for SerumError {}
+
+/// Exit status of a failed command, by the first categorized error of the chain.
+pub fn exit_code(err: &anyhow::Error) -> i32 {
+    for cause in err.chain() {
+        if let Some(err) = cause.downcast_ref::<SerumError>() {
+            return match err {
+                SerumError::Validation(_) => 2,
+                SerumError::InsufficientFunds(_) => 4,
+                SerumError::ThresholdNotMet(_) => 5,
+            };
+        }
+        if cause.is::<anchor_client::ClientError>() || cause.is::<ClientError>() {
+            return 3;
+        }
+    }
+    1
+}
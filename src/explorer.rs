@@ -0,0 +1,62 @@
+//! Links to transactions on the block explorers.
+
+use anchor_client::{solana_sdk::signature::Signature, Cluster};
+use clap::ValueEnum;
+use reqwest::Url;
+
+use crate::self_test::is_local;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Explorer {
+    /// explorer.solana.com
+    Explorer,
+    /// solscan.io
+    Solscan,
+    /// solana.fm
+    #[value(name = "solanafm")]
+    SolanaFm,
+}
+
+impl Explorer {
+    pub fn transaction_url(self, signature: &Signature, cluster: &Cluster) -> String {
+        let (base, cluster_params) = match self {
+            Explorer::Explorer => ("https://explorer.solana.com/tx/", custom_params(cluster)),
+            Explorer::Solscan => ("https://solscan.io/tx/", custom_params(cluster)),
+            Explorer::SolanaFm => ("https://solana.fm/tx/", solana_fm_params(cluster)),
+        };
+        let url = format!("{}{}", base, signature);
+        Url::parse_with_params(&url, &cluster_params)
+            .expect("valid explorer URL")
+            .to_string()
+    }
+}
+
+/// Cluster parameters of Solana Explorer and Solscan, which can also point
+/// at any RPC endpoint.
+fn custom_params(cluster: &Cluster) -> Vec<(&'static str, String)> {
+    match cluster {
+        Cluster::Mainnet => vec![],
+        Cluster::Devnet => vec![("cluster", "devnet".to_string())],
+        Cluster::Testnet => vec![("cluster", "testnet".to_string())],
+        _ => vec![
+            ("cluster", "custom".to_string()),
+            ("customUrl", cluster.url().to_string()),
+        ],
+    }
+}
+
+/// Cluster parameters of SolanaFM, which only knows the public clusters and
+/// a local validator.
+fn solana_fm_params(cluster: &Cluster) -> Vec<(&'static str, String)> {
+    let name = match cluster {
+        Cluster::Mainnet => "mainnet-alpha",
+        Cluster::Devnet => "devnet-solana",
+        Cluster::Testnet => "testnet-solana",
+        _ if is_local(cluster) => "localnet-solana",
+        _ => {
+            log::warn!("SolanaFM can't show transactions of {}", cluster.url());
+            "mainnet-alpha"
+        }
+    };
+    vec![("cluster", name.to_string())]
+}
@@ -0,0 +1,268 @@
+//! Reproducible fixtures for testing the decoding, status and execution logic
+//! without a validator: keypairs derived from a seed, a multisig account owned
+//! by some of them and a transaction account proposing a token transfer from
+//! the multisig, approved by its proposer.
+
+use anchor_client::{
+    anchor_lang::{solana_program::hash, AccountSerialize},
+    solana_sdk::{
+        pubkey::Pubkey,
+        rent::Rent,
+        signature::{keypair_from_seed, Keypair, Signer},
+    },
+};
+use base64::{engine::Engine, prelude::BASE64_STANDARD};
+use serde::Serialize;
+use spl_token::instruction as token_instruction;
+
+use crate::{derive_multisig_signer, MULTISIG_ACCOUNT_SIZE, TRANSACTION_ACCOUNT_SIZE};
+
+const OWNERS: [&str; 3] = ["owner-0", "owner-1", "owner-2"];
+const THRESHOLD: u64 = 2;
+const TRANSFER_AMOUNT: u64 = 1_000_000;
+
+#[derive(Serialize)]
+pub struct Fixture {
+    seed: u64,
+    program_id: String,
+    keypairs: Vec<FixtureKeypair>,
+    multisig: FixtureAccount,
+    /// Signer of the multisig, owning the transferred token account.
+    multisig_signer: String,
+    transaction: FixtureAccount,
+}
+
+#[derive(Serialize)]
+struct FixtureKeypair {
+    name: &'static str,
+    pubkey: String,
+    /// Secret key bytes, as written by solana-keygen.
+    secret_key: Vec<u8>,
+}
+
+/// Account as `getAccountInfo` returns it.
+#[derive(Serialize)]
+struct FixtureAccount {
+    address: String,
+    owner: String,
+    lamports: u64,
+    /// Base64 account data.
+    data: String,
+}
+
+/// Derives the keypair `name` of the fixture from `seed`.
+fn keypair(seed: u64, name: &str) -> anyhow::Result<Keypair> {
+    let seed = hash::hash(format!("serum fixture {} {}", seed, name).as_bytes());
+    keypair_from_seed(seed.as_ref()).map_err(|e| anyhow::Error::msg(e.to_string()))
+}
+
+fn account(
+    address: Pubkey,
+    program_id: &Pubkey,
+    state: &impl AccountSerialize,
+    size: usize,
+) -> anyhow::Result<FixtureAccount> {
+    let mut data = Vec::with_capacity(size);
+    state.try_serialize(&mut data)?;
+    data.resize(size, 0);
+    Ok(FixtureAccount {
+        address: address.to_string(),
+        owner: program_id.to_string(),
+        lamports: Rent::default().minimum_balance(size),
+        data: BASE64_STANDARD.encode(data),
+    })
+}
+
+/// Generates the fixture of `seed` for the multisig program `program_id`.
+/// The same seed always gives the same fixture.
+pub fn generate(seed: u64, program_id: &Pubkey) -> anyhow::Result<Fixture> {
+    let names = OWNERS
+        .iter()
+        .chain(&["multisig", "transaction", "source", "destination"]);
+    let mut keypairs = Vec::new();
+    for name in names {
+        keypairs.push((*name, keypair(seed, name)?));
+    }
+    let pubkey = |name: &str| {
+        keypairs
+            .iter()
+            .find(|(keypair_name, _)| *keypair_name == name)
+            .map(|(_, keypair)| keypair.pubkey())
+            .expect("every fixture keypair is generated")
+    };
+
+    let multisig = pubkey("multisig");
+    let (multisig_signer, nonce) = derive_multisig_signer(&multisig, program_id);
+    let multisig_account = coral_multisig::Multisig {
+        owners: OWNERS.iter().map(|name| pubkey(name)).collect(),
+        threshold: THRESHOLD,
+        nonce,
+        owner_set_seqno: 0,
+    };
+
+    let transfer = token_instruction::transfer(
+        &spl_token::id(),
+        &pubkey("source").to_bytes().into(),
+        &pubkey("destination").to_bytes().into(),
+        &multisig_signer.to_bytes().into(),
+        &[],
+        TRANSFER_AMOUNT,
+    )?;
+    let transaction_account = coral_multisig::Transaction {
+        multisig,
+        program_id: transfer.program_id.to_bytes().into(),
+        accounts: transfer
+            .accounts
+            .iter()
+            .map(|acc| coral_multisig::TransactionAccount {
+                pubkey: acc.pubkey.to_bytes().into(),
+                is_signer: acc.is_signer,
+                is_writable: acc.is_writable,
+            })
+            .collect(),
+        data: transfer.data,
+        // Approved by its proposer, the first owner.
+        signers: OWNERS.iter().enumerate().map(|(i, _)| i == 0).collect(),
+        did_execute: false,
+        owner_set_seqno: 0,
+    };
+
+    Ok(Fixture {
+        seed,
+        program_id: program_id.to_string(),
+        multisig: account(
+            multisig,
+            program_id,
+            &multisig_account,
+            MULTISIG_ACCOUNT_SIZE,
+        )?,
+        multisig_signer: multisig_signer.to_string(),
+        transaction: account(
+            pubkey("transaction"),
+            program_id,
+            &transaction_account,
+            TRANSACTION_ACCOUNT_SIZE,
+        )?,
+        keypairs: keypairs
+            .iter()
+            .map(|(name, keypair)| FixtureKeypair {
+                name: *name,
+                pubkey: keypair.pubkey().to_string(),
+                secret_key: keypair.to_bytes().to_vec(),
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_client::{
+        anchor_lang::AccountDeserialize,
+        solana_sdk::{commitment_config::CommitmentConfig, compute_budget},
+        Client, Cluster,
+    };
+    use serum_common::multisig::DEFAULT_PID;
+
+    use super::*;
+    use crate::{decode, execute_message, ApprovalCount, ComputeBudgetArgs, SignerArg};
+
+    fn program_id() -> Pubkey {
+        DEFAULT_PID.parse().unwrap()
+    }
+
+    fn fixture() -> Fixture {
+        generate(7, &program_id()).unwrap()
+    }
+
+    fn keypair_named(fixture: &Fixture, name: &str) -> Keypair {
+        let keypair = fixture.keypairs.iter().find(|k| k.name == name).unwrap();
+        Keypair::from_bytes(&keypair.secret_key).unwrap()
+    }
+
+    fn deserialize<T: AccountDeserialize>(account: &FixtureAccount) -> T {
+        let data = BASE64_STANDARD.decode(&account.data).unwrap();
+        T::try_deserialize(&mut data.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn is_reproducible() {
+        let (a, b) = (fixture(), fixture());
+        assert_eq!(a.multisig.address, b.multisig.address);
+        assert_eq!(a.transaction.data, b.transaction.data);
+        assert_ne!(
+            a.multisig.address,
+            generate(8, &program_id()).unwrap().multisig.address
+        );
+    }
+
+    #[test]
+    fn decodes_the_proposed_transfer() {
+        let fixture = fixture();
+        let transaction: coral_multisig::Transaction = deserialize(&fixture.transaction);
+        assert_eq!(
+            transaction.program_id,
+            Pubkey::from(spl_token::id().to_bytes())
+        );
+        assert_eq!(
+            decode::describe_instruction(&transaction.program_id, &program_id(), &transaction.data),
+            format!("token: Transfer of {} base units", TRANSFER_AMOUNT)
+        );
+    }
+
+    #[test]
+    fn counts_the_proposer_approval() {
+        let fixture = fixture();
+        let multisig: coral_multisig::Multisig = deserialize(&fixture.multisig);
+        let transaction: coral_multisig::Transaction = deserialize(&fixture.transaction);
+        let count = ApprovalCount::new(&multisig, &transaction);
+        assert_eq!((count.approved, count.threshold), (1, THRESHOLD));
+        assert!(!count.is_met());
+    }
+
+    #[test]
+    fn execute_keeps_the_compute_budget_in_the_outer_message() {
+        let fixture = fixture();
+        let transaction: coral_multisig::Transaction = deserialize(&fixture.transaction);
+        let payer = keypair_named(&fixture, "owner-0");
+        let client =
+            Client::new_with_options(Cluster::Localnet, &payer, CommitmentConfig::confirmed());
+        let program = client.program(program_id()).unwrap();
+        let signer = SignerArg::new(payer.pubkey(), Pubkey::new_unique(), hash::Hash::default());
+        let compute_budget = ComputeBudgetArgs {
+            compute_unit_limit: Some(400_000),
+            compute_unit_price: Some(1_000),
+        };
+        let message = execute_message(
+            &program,
+            &signer,
+            fixture.multisig.address.parse().unwrap(),
+            fixture.transaction.address.parse().unwrap(),
+            &transaction,
+            &compute_budget,
+        )
+        .unwrap();
+
+        let programs: Vec<_> = message
+            .instructions
+            .iter()
+            .map(|ix| message.account_keys[ix.program_id_index as usize])
+            .collect();
+        // Advance the nonce, set the limit and the price, then execute.
+        assert_eq!(programs.len(), 4);
+        assert!(programs[1..3].iter().all(|id| *id == compute_budget::id()));
+        assert_eq!(programs[3], program_id());
+        assert_eq!(
+            decode::multisig_instruction_name(&message.instructions[3].data),
+            Some("execute_transaction")
+        );
+        // The stored instruction is left as proposed.
+        assert_eq!(
+            transaction.program_id,
+            Pubkey::from(spl_token::id().to_bytes())
+        );
+        assert!(!transaction
+            .accounts
+            .iter()
+            .any(|acc| acc.pubkey == compute_budget::id()));
+    }
+}
@@ -0,0 +1,306 @@
+//! Decoding of instruction arguments with an Anchor IDL, in both the current
+//! format with explicit discriminators and the legacy one.
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use anchor_client::{
+    anchor_lang::idl::IdlAccount,
+    solana_sdk::{pubkey::Pubkey, signature::Keypair},
+    Program,
+};
+use base64::{engine::Engine, prelude::BASE64_STANDARD};
+use flate2::read::ZlibDecoder;
+use serde_json::{Map, Value};
+
+use crate::{decode, rpc};
+
+pub struct Idl {
+    address: Option<String>,
+    instructions: Vec<IdlInstruction>,
+    /// Type definitions by name.
+    types: HashMap<String, Value>,
+}
+
+struct IdlInstruction {
+    name: String,
+    discriminator: Vec<u8>,
+    /// Argument names and types.
+    args: Vec<(String, Value)>,
+}
+
+fn malformed(what: &str) -> anyhow::Error {
+    anyhow::Error::msg(format!("malformed IDL: {}", what))
+}
+
+/// Converts the camelCase names of legacy IDLs to the snake_case used in discriminators.
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            snake.push('_');
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+impl Idl {
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let idl: Value = serde_json::from_str(json)?;
+        let instructions = idl["instructions"]
+            .as_array()
+            .ok_or_else(|| malformed("no instructions"))?
+            .iter()
+            .map(|ix| {
+                let name = ix["name"]
+                    .as_str()
+                    .ok_or_else(|| malformed("instruction without a name"))?
+                    .to_string();
+                let discriminator = match ix["discriminator"].as_array() {
+                    Some(bytes) => bytes
+                        .iter()
+                        .map(|b| b.as_u64().map(|b| b as u8))
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| malformed("invalid discriminator"))?,
+                    None => decode::sighash(&snake_case(&name)).to_vec(),
+                };
+                let args = ix["args"]
+                    .as_array()
+                    .map(Vec::as_slice)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|arg| {
+                        Ok((
+                            arg["name"]
+                                .as_str()
+                                .ok_or_else(|| malformed("argument without a name"))?
+                                .to_string(),
+                            arg["type"].clone(),
+                        ))
+                    })
+                    .collect::<anyhow::Result<_>>()?;
+                Ok(IdlInstruction {
+                    name,
+                    discriminator,
+                    args,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let types = idl["types"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|ty| Some((ty["name"].as_str()?.to_string(), ty["type"].clone())))
+            .collect();
+        Ok(Self {
+            address: idl["address"]
+                .as_str()
+                .or(idl["metadata"]["address"].as_str())
+                .map(ToString::to_string),
+            instructions,
+            types,
+        })
+    }
+
+    /// Fetches the IDL a program published in its IDL account.
+    pub async fn fetch(program: &Program<&Keypair>, program_id: &Pubkey) -> anyhow::Result<Self> {
+        let address = IdlAccount::address(program_id);
+        log::info!("fetching IDL account {}", address);
+        let rpc = program.async_rpc();
+        let data = rpc::retry(|| rpc.get_account_data(&address))
+            .await
+            .map_err(|e| {
+                anyhow::Error::msg(format!(
+                    "program {} has no IDL account {}: {}",
+                    program_id, address, e
+                ))
+            })?;
+        // Discriminator, authority and the length of the compressed IDL.
+        let len_bytes = data
+            .get(40..44)
+            .ok_or_else(|| malformed("IDL account too short"))?;
+        let len = u32::from_le_bytes(len_bytes.try_into().expect("4 bytes")) as usize;
+        let compressed = data
+            .get(44..44 + len)
+            .ok_or_else(|| malformed("IDL account too short"))?;
+        let mut json = String::new();
+        ZlibDecoder::new(compressed).read_to_string(&mut json)?;
+        Self::from_json(&json)
+    }
+
+    /// Loads the IDL of `program_id` from a JSON file, rejecting one that
+    /// declares another program.
+    pub fn from_file(path: &str, program_id: &Pubkey) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::Error::msg(format!("failed to read {}: {}", path, e)))?;
+        let idl = Self::from_json(&json)?;
+        match idl.address() {
+            Some(address) if address != program_id.to_string() => {
+                return Err(anyhow::Error::msg(format!(
+                    "IDL {} is for program {}, not {}",
+                    path, address, program_id
+                )));
+            }
+            Some(_) => {}
+            None => log::warn!("IDL {} declares no program address", path),
+        }
+        Ok(idl)
+    }
+
+    /// Program address the IDL declares, if any.
+    pub fn address(&self) -> Option<&str> {
+        self.address.as_deref()
+    }
+
+    /// Decodes the instruction name and arguments, or `None` if no
+    /// instruction of the IDL matches the discriminator.
+    pub fn decode_instruction(&self, data: &[u8]) -> Option<anyhow::Result<(&str, Value)>> {
+        let ix = self
+            .instructions
+            .iter()
+            .find(|ix| data.starts_with(&ix.discriminator))?;
+        let mut rest = &data[ix.discriminator.len()..];
+        let args = ix
+            .args
+            .iter()
+            .map(|(name, ty)| Ok((name.clone(), self.decode_value(ty, &mut rest)?)))
+            .collect::<anyhow::Result<Map<_, _>>>();
+        Some(args.map(|args| (ix.name.as_str(), Value::Object(args))))
+    }
+
+    fn decode_value(&self, ty: &Value, data: &mut &[u8]) -> anyhow::Result<Value> {
+        if let Some(name) = ty.as_str() {
+            return decode_primitive(name, data);
+        }
+        if let Some(inner) = ty.get("vec") {
+            let len = u32::from_le_bytes(take(data, 4)?.try_into().expect("4 bytes"));
+            return (0..len)
+                .map(|_| self.decode_value(inner, data))
+                .collect::<anyhow::Result<Vec<_>>>()
+                .map(Value::Array);
+        }
+        if let Some(inner) = ty.get("option") {
+            return match take(data, 1)?[0] {
+                0 => Ok(Value::Null),
+                _ => self.decode_value(inner, data),
+            };
+        }
+        if let Some([inner, len]) = ty.get("array").and_then(Value::as_array).map(Vec::as_slice) {
+            let len = len.as_u64().ok_or_else(|| malformed("array length"))?;
+            return (0..len)
+                .map(|_| self.decode_value(inner, data))
+                .collect::<anyhow::Result<Vec<_>>>()
+                .map(Value::Array);
+        }
+        if let Some(defined) = ty.get("defined") {
+            let name = defined
+                .as_str()
+                .or(defined["name"].as_str())
+                .ok_or_else(|| malformed("defined type without a name"))?;
+            let definition = self
+                .types
+                .get(name)
+                .ok_or_else(|| malformed(&format!("type {} is not defined", name)))?;
+            return self.decode_defined(definition, data);
+        }
+        Err(malformed(&format!("unsupported type {}", ty)))
+    }
+
+    fn decode_defined(&self, definition: &Value, data: &mut &[u8]) -> anyhow::Result<Value> {
+        match definition["kind"].as_str() {
+            Some("struct") => self.decode_fields(&definition["fields"], data),
+            Some("enum") => {
+                let index = take(data, 1)?[0] as usize;
+                let variant = definition["variants"]
+                    .get(index)
+                    .ok_or_else(|| malformed("enum variant out of range"))?;
+                let name = variant["name"].as_str().unwrap_or_default().to_string();
+                if variant["fields"].is_null() {
+                    return Ok(Value::String(name));
+                }
+                let fields = self.decode_fields(&variant["fields"], data)?;
+                Ok(Value::Object(Map::from_iter([(name, fields)])))
+            }
+            _ => Err(malformed("unsupported type definition")),
+        }
+    }
+
+    /// Decodes named fields into an object and tuple fields into an array.
+    fn decode_fields(&self, fields: &Value, data: &mut &[u8]) -> anyhow::Result<Value> {
+        let fields = fields.as_array().map(Vec::as_slice).unwrap_or_default();
+        if fields.iter().all(|field| field["name"].is_string()) {
+            fields
+                .iter()
+                .map(|field| {
+                    Ok((
+                        field["name"].as_str().expect("checked").to_string(),
+                        self.decode_value(&field["type"], data)?,
+                    ))
+                })
+                .collect::<anyhow::Result<Map<_, _>>>()
+                .map(Value::Object)
+        } else {
+            fields
+                .iter()
+                .map(|ty| self.decode_value(ty, data))
+                .collect::<anyhow::Result<Vec<_>>>()
+                .map(Value::Array)
+        }
+    }
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> anyhow::Result<&'a [u8]> {
+    if data.len() < len {
+        return Err(anyhow::Error::msg(
+            "instruction data is shorter than its IDL layout",
+        ));
+    }
+    let (taken, rest) = data.split_at(len);
+    *data = rest;
+    Ok(taken)
+}
+
+fn decode_primitive(name: &str, data: &mut &[u8]) -> anyhow::Result<Value> {
+    macro_rules! int {
+        ($ty:ty) => {
+            <$ty>::from_le_bytes(
+                take(data, std::mem::size_of::<$ty>())?
+                    .try_into()
+                    .expect("sized"),
+            )
+        };
+    }
+    Ok(match name {
+        "bool" => Value::Bool(take(data, 1)?[0] != 0),
+        "u8" => int!(u8).into(),
+        "i8" => int!(i8).into(),
+        "u16" => int!(u16).into(),
+        "i16" => int!(i16).into(),
+        "u32" => int!(u32).into(),
+        "i32" => int!(i32).into(),
+        "u64" => int!(u64).into(),
+        "i64" => int!(i64).into(),
+        // Beyond the integers JSON numbers hold exactly.
+        "u128" => int!(u128).to_string().into(),
+        "i128" => int!(i128).to_string().into(),
+        "f32" => f32::from_le_bytes(take(data, 4)?.try_into().expect("sized")).into(),
+        "f64" => f64::from_le_bytes(take(data, 8)?.try_into().expect("sized")).into(),
+        "publicKey" | "pubkey" => Pubkey::try_from(take(data, 32)?)
+            .expect("32 bytes")
+            .to_string()
+            .into(),
+        "string" => {
+            let len = int!(u32) as usize;
+            String::from_utf8(take(data, len)?.to_vec())?.into()
+        }
+        "bytes" => {
+            let len = int!(u32) as usize;
+            BASE64_STANDARD.encode(take(data, len)?).into()
+        }
+        _ => return Err(malformed(&format!("unsupported type {}", name))),
+    })
+}
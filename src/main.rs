@@ -1,48 +1,402 @@
+mod attestation;
+mod bundle;
+mod color;
+mod config;
+mod decode;
+mod error;
+mod explorer;
+mod fixture;
+mod idl;
+mod manifest;
+mod plan;
+mod qr;
+mod review;
+mod rpc;
+mod self_test;
+mod signing_session;
+mod tokens;
+
+use std::collections::{HashMap, HashSet};
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
 use anchor_client::{
-    anchor_lang::{prelude::AccountMeta, solana_program::hash},
+    anchor_lang::{prelude::AccountMeta, solana_program::hash, AccountDeserialize},
+    solana_client::{
+        nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient},
+        nonce_utils,
+        rpc_config::{
+            RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig,
+            RpcTransactionConfig, RpcTransactionLogsConfig, RpcTransactionLogsFilter,
+        },
+        rpc_filter::{Memcmp, RpcFilterType},
+        rpc_request::TokenAccountsFilter,
+    },
     solana_sdk::{
+        account::Account,
+        bpf_loader_upgradeable::{self, UpgradeableLoaderState},
         commitment_config::CommitmentConfig,
+        compute_budget::ComputeBudgetInstruction,
         instruction::Instruction,
         message::Message,
+        native_token::sol_to_lamports,
+        nonce::State as NonceState,
+        offchain_message::OffchainMessage,
+        packet::PACKET_DATA_SIZE,
         pubkey::Pubkey as AnchorPubkey,
-        signature::{Keypair, Signature, Signer},
-        system_instruction, sysvar,
+        rent::Rent,
+        sanitize::Sanitize,
+        signature::{write_keypair_file, Keypair, Signature, Signer},
+        system_instruction::{self, SystemInstruction},
+        system_program, sysvar,
         transaction::Transaction,
     },
-    Client, Cluster,
+    Client, Cluster, Program,
 };
 use base64::{engine::Engine, prelude::BASE64_STANDARD};
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use color::style;
+use coral_multisig::accounts as multisig_accounts;
 use coral_multisig::instruction as multisig_instructions;
-use coral_multisig::{accounts as multisig_accounts, TransactionAccount};
-use crossterm::style::{style, Stylize};
-use spl_token::instruction::{self as token_instruction, TokenInstruction};
+use crossterm::style::Stylize;
+use error::SerumError;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serum_common::keypair::{decode_hex, KeypairFormat};
+use serum_common::multisig::DEFAULT_PID;
+use serum_common::offchain::{self, offchain_text, parse_domain};
+use solana_account_decoder::{parse_token::UiTokenAccount, UiAccountData};
+use solana_transaction_status::UiTransactionEncoding;
+use spl_associated_token_account_client::{
+    address::get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+use spl_token::{
+    instruction::{self as token_instruction, TokenInstruction},
+    solana_program::{
+        instruction::{AccountMeta as SplAccountMeta, Instruction as SplInstruction},
+        program_pack::Pack,
+    },
+};
+use spl_token_2022::{
+    extension::{
+        transfer_fee::{
+            instruction::{self as transfer_fee_instruction, TransferFeeInstruction},
+            TransferFeeConfig,
+        },
+        BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+    },
+    instruction::TokenInstruction as Token2022Instruction,
+};
+
+/// Size of the multisig accounts created by `CreateMultisig`.
+const MULTISIG_ACCOUNT_SIZE: usize = 500;
+/// Size of the transaction accounts created by the transaction proposals.
+const TRANSACTION_ACCOUNT_SIZE: usize = 500;
+/// Bytes a stored instruction account takes: pubkey and the two flags.
+const TRANSACTION_ACCOUNT_META_SIZE: usize = 34;
+
+/// How many instruction accounts a transaction account stores along with
+/// `data_len` bytes of instruction data and the approvals of `owners` owners.
+fn transaction_account_capacity(data_len: usize, owners: usize) -> usize {
+    // Discriminator, multisig, program id, the lengths of the accounts, data
+    // and signers vectors, did_execute and owner_set_seqno.
+    let fixed = 8 + 32 + 32 + 4 + 4 + 4 + 1 + 4;
+    TRANSACTION_ACCOUNT_SIZE.saturating_sub(fixed + data_len + owners)
+        / TRANSACTION_ACCOUNT_META_SIZE
+}
 
 #[derive(Parser)]
 struct Cli {
-    #[arg(
-        long = "pid",
-        default_value = "AAHT26ecV3FEeFmL2gDZW6FfEqjPkghHbAkNZGqwT8Ww" // Devnet: msigUdDBsR4zSUYqYEDrc1LcgtmuSDDM7KxpRUXNC6U
-    )]
-    pid: AnchorPubkey,
-    #[arg(long = "cluster", default_value_t = Cluster::Devnet)]
-    cluster: Cluster,
+    /// Multisig program id [default: the one registered for the multisig, then $SERUM_PID, then
+    /// the profile's, then the devnet deployment]
+    #[arg(long = "pid")]
+    pid: Option<AnchorPubkey>,
+    /// Cluster name, `localhost` or RPC URL [default: devnet]
+    #[arg(long = "cluster", value_parser = config::parse_cluster)]
+    cluster: Option<Cluster>,
 
     #[arg(short = 'k', long = "private-key")]
     key_file: Option<String>,
+    /// Encoding of the key files
+    #[arg(long = "keypair-format", value_enum, default_value_t = KeypairFormat::JsonArray)]
+    keypair_format: KeypairFormat,
+
+    /// Profile from the config file providing defaults for --pid, --cluster and --private-key
+    #[arg(long = "profile")]
+    profile: Option<String>,
+    /// Config file with the profiles [default: ~/.config/serum/config.toml]
+    #[arg(long = "config")]
+    config: Option<PathBuf>,
+
+    /// Registry of known multisigs, providing --pid and --cluster for the --multisig of the
+    /// command [default: ~/.config/serum/registry.json if it exists]
+    #[arg(long = "registry")]
+    registry: Option<PathBuf>,
+
+    /// Do not query the cluster, values normally fetched from it must be given explicitly
+    #[arg(long = "offline", default_value_t = false)]
+    offline: bool,
+    /// Known decimals of a mint, as MINT=DECIMALS; may be repeated
+    #[arg(long = "decimals", value_parser = parse_mint_decimals)]
+    decimals: Vec<(AnchorPubkey, u8)>,
+    #[arg(long = "output-format", value_enum, default_value_t = OutputFormat::Message)]
+    output_format: OutputFormat,
+    /// Encoding of printed messages and transactions, and of the messages read by Submit and
+    /// Decode [default: base64, base58 for partial transactions]
+    #[arg(long = "encoding", value_enum)]
+    encoding: Option<Encoding>,
+    /// Also write each built message as a signing session holding the signatures made locally,
+    /// to signing-session-<HASH>.json, for AddSignatures and Submit --from-session
+    #[arg(long = "session", default_value_t = false)]
+    session: bool,
+    /// Also print each message or partial transaction as the chunks of a sequence of QR codes,
+    /// for Submit --from-qr
+    #[arg(long = "qr", default_value_t = false)]
+    qr: bool,
+    /// Also print the SHA-256 hash of each message, for comparing it with the signers out of band
+    #[arg(long = "print-message-hash", default_value_t = false)]
+    print_message_hash: bool,
+    /// How many times to retry a rate limited request or a failed submission where it is safe to do so
+    #[arg(long = "rpc-retries", default_value_t = 3)]
+    rpc_retries: u32,
+    /// Fail instead of warning on anomalies: an advanced nonce or an executed transaction (even
+    /// with --force), missing approvals, owners changed since the proposal, breaking rent exemption or draining the
+    /// signer, balances not checked offline, a missing multisig signer, an upcoming transfer
+    /// fee change, a failed simulation and transactions at or over the size limit
+    #[arg(long = "strict", default_value_t = false)]
+    strict: bool,
+    /// Memo appended to the outer transaction creating or executing a proposal, e.g. a ticket
+    /// number, shown by explorers and covered by the signatures
+    #[arg(long = "outer-memo")]
+    outer_memo: Option<String>,
+    /// Commitment of the state read from the cluster and of the submission confirmations
+    #[arg(long = "commitment", value_enum, default_value_t = Commitment::Processed)]
+    commitment: Commitment,
+    /// Block explorer linked for the submitted transactions
+    #[arg(long = "explorer", value_enum, default_value_t = explorer::Explorer::Explorer)]
+    explorer: explorer::Explorer,
+    /// Abort the command if it takes longer than this many seconds, confirmations included
+    #[arg(long = "timeout")]
+    timeout: Option<u64>,
+    /// Allow signing and submitting transactions on mainnet without a prompt. Custom RPC URLs
+    /// count as mainnet when their genesis hash is mainnet's, or offline unless they are local
+    #[arg(long = "mainnet-confirm", default_value_t = false)]
+    mainnet_confirm: bool,
+    /// Log the intermediate steps to stderr; repeat for more detail
+    #[arg(short = 'v', long = "verbose", action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Disable styled output, also implied by NO_COLOR or a non-terminal stdout
+    #[arg(long = "no-color", default_value_t = false)]
+    no_color: bool,
 
     #[command(subcommand)]
     command: Command,
 }
 
-#[derive(Args)]
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    /// Base64 message, to be signed by each signer separately
+    Message,
+    /// Base58 transaction with the locally available signatures filled in,
+    /// to be completed with `solana sign`
+    PartialTransaction,
+    /// JSON signing request with the message, its instructions and the required
+    /// signers, written to a file for `solana-signer --request`
+    Manifest,
+}
+
+/// Text encoding of serialized messages and transactions.
+#[derive(Clone, Copy, ValueEnum)]
+enum Encoding {
+    Base64,
+    Base58,
+    Hex,
+}
+
+impl Encoding {
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Base64 => "base64",
+            Encoding::Base58 => "base58",
+            Encoding::Hex => "hex",
+        }
+    }
+
+    fn encode(self, data: &[u8]) -> String {
+        match self {
+            Encoding::Base64 => BASE64_STANDARD.encode(data),
+            Encoding::Base58 => bs58::encode(data).into_string(),
+            Encoding::Hex => decode::hex(data),
+        }
+    }
+
+    fn decode(self, s: &str) -> anyhow::Result<Vec<u8>> {
+        let s = s.trim();
+        let data = match self {
+            Encoding::Base64 => BASE64_STANDARD.decode(s).ok(),
+            Encoding::Base58 => bs58::decode(s).into_vec().ok(),
+            Encoding::Hex => decode_hex(s),
+        };
+        data.ok_or_else(|| {
+            SerumError::Validation(format!(
+                "the input is not {}, check --encoding",
+                self.name()
+            ))
+            .into()
+        })
+    }
+
+    /// Decodes a message, rejecting input that decodes to anything but
+    /// exactly one message.
+    fn decode_message(self, s: &str) -> anyhow::Result<Message> {
+        let data = self.decode(s)?;
+        let message: Message = bincode::deserialize(&data).map_err(|_| {
+            SerumError::Validation(format!(
+                "the {} input is not a message, check --encoding",
+                self.name()
+            ))
+        })?;
+        if message.serialize().len() != data.len() {
+            return Err(SerumError::Validation(format!(
+                "the {} input has bytes past the message, check --encoding",
+                self.name()
+            ))
+            .into());
+        }
+        sanitize_message(&message)?;
+        Ok(message)
+    }
+
+    /// Decodes a message, or a transaction holding its signatures as
+    /// assembled by `solana-signer --assemble`.
+    fn decode_signed(self, s: &str) -> anyhow::Result<(Message, Option<Vec<Signature>>)> {
+        let data = self.decode(s)?;
+        if let Ok(tx) = bincode::deserialize::<Transaction>(&data) {
+            if tx.signatures.len() == tx.message.header.num_required_signatures as usize
+                && bincode::serialized_size(&tx)? == data.len() as u64
+            {
+                sanitize_message(&tx.message)?;
+                return Ok((tx.message, Some(tx.signatures)));
+            }
+        }
+        Ok((self.decode_message(s)?, None))
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Commitment {
+    Processed,
+    Confirmed,
+    Finalized,
+}
+
+impl Commitment {
+    fn config(self) -> CommitmentConfig {
+        match self {
+            Commitment::Processed => CommitmentConfig::processed(),
+            Commitment::Confirmed => CommitmentConfig::confirmed(),
+            Commitment::Finalized => CommitmentConfig::finalized(),
+        }
+    }
+}
+
+/// Fee payer of the built transaction, which advances its durable nonce.
+///
+/// The nonce account has to be authorized by the signer itself. Nonce
+/// authorities derived from a program, such as a multisig signer, aren't
+/// supported: advancing the nonce is the first instruction of the transaction
+/// and can't be signed for by a program.
+#[derive(Args, Clone)]
 struct SignerArg {
     #[arg(long = "signer")]
     signer: AnchorPubkey,
-    #[arg(long = "nonce-account")]
-    nonce_account: AnchorPubkey,
-    #[arg(long = "nonce")]
-    nonce: hash::Hash,
+    #[arg(long = "nonce-account", required_unless_present = "auto_nonce")]
+    nonce_account: Option<AnchorPubkey>,
+    #[arg(long = "nonce", required_unless_present = "auto_nonce")]
+    nonce: Option<hash::Hash>,
+    /// Create a fresh nonce account authorized by the signer, paid by the local key, and use
+    /// its nonce instead of --nonce-account and --nonce
+    #[arg(long = "auto-nonce", default_value_t = false, conflicts_with_all = ["nonce_account", "nonce"])]
+    auto_nonce: bool,
+    /// Account paying the rent of the account the command creates, signing along with the
+    /// signer [default: the signer]
+    #[arg(long = "rent-payer")]
+    rent_payer: Option<AnchorPubkey>,
+    /// Memo of the outer transaction, from the global --outer-memo.
+    #[arg(skip)]
+    outer_memo: Option<String>,
+}
+
+impl SignerArg {
+    /// Signer advancing `nonce_account`, whose current value is `nonce`.
+    fn new(signer: AnchorPubkey, nonce_account: AnchorPubkey, nonce: hash::Hash) -> Self {
+        Self {
+            signer,
+            nonce_account: Some(nonce_account),
+            nonce: Some(nonce),
+            auto_nonce: false,
+            rent_payer: None,
+            outer_memo: None,
+        }
+    }
+
+    fn rent_payer(&self) -> AnchorPubkey {
+        self.rent_payer.unwrap_or(self.signer)
+    }
+
+    /// Nonce account the transaction advances, given or created by --auto-nonce.
+    fn nonce_account(&self) -> anyhow::Result<AnchorPubkey> {
+        self.nonce_account.ok_or_else(|| self.missing_nonce())
+    }
+
+    /// Current nonce of the nonce account, given or read by --auto-nonce.
+    fn nonce(&self) -> anyhow::Result<hash::Hash> {
+        self.nonce.ok_or_else(|| self.missing_nonce())
+    }
+
+    fn missing_nonce(&self) -> anyhow::Error {
+        SerumError::Validation(format!(
+            "no nonce for the signer {}, pass --nonce-account and --nonce, or --auto-nonce",
+            self.signer
+        ))
+        .into()
+    }
+
+    /// Memo instruction to append to the outer transaction. It isn't stored
+    /// in the transaction account, so it only annotates this transaction.
+    fn memo_instruction(&self) -> Option<Instruction> {
+        let memo = self.outer_memo.as_ref()?;
+        Some(spl_memo::build_memo(memo.as_bytes(), &[]))
+    }
+}
+
+/// Compute budget of an execution, for stored instructions with expensive CPIs.
+#[derive(Args, Clone, Default)]
+struct ComputeBudgetArgs {
+    /// Compute unit limit of the execution transaction
+    #[arg(long = "compute-unit-limit")]
+    compute_unit_limit: Option<u32>,
+    /// Priority fee of the execution transaction, in micro-lamports per compute unit
+    #[arg(long = "compute-unit-price")]
+    compute_unit_price: Option<u64>,
+}
+
+impl ComputeBudgetArgs {
+    /// Instructions to add to the outer transaction. They must never be stored
+    /// in the transaction account, where they would be invoked through CPI.
+    fn instructions(&self) -> Vec<Instruction> {
+        self.compute_unit_limit
+            .map(ComputeBudgetInstruction::set_compute_unit_limit)
+            .into_iter()
+            .chain(
+                self.compute_unit_price
+                    .map(ComputeBudgetInstruction::set_compute_unit_price),
+            )
+            .collect()
+    }
 }
 
 #[derive(Subcommand)]
@@ -54,8 +408,19 @@ enum Command {
         signer: SignerArg,
         #[arg(long = "signers")]
         signers: Vec<AnchorPubkey>,
-        #[arg(long = "threshold")]
-        threshold: u64,
+        #[arg(long = "threshold", required_unless_present = "interactive")]
+        threshold: Option<u64>,
+        /// Prompt for the owners and the threshold, confirming them before building the transaction
+        #[arg(
+            long = "interactive",
+            default_value_t = false,
+            conflicts_with_all = ["signers", "threshold"]
+        )]
+        interactive: bool,
+        /// Mint, or symbol of a well-known mainnet token, whose associated token account of the
+        /// multisig PDA to preview before the transaction; may be repeated
+        #[arg(long = "preview-mint", value_parser = tokens::parse_mint)]
+        preview_mints: Vec<tokens::MintArg>,
     },
     /// Create a token transfer transaction
     CreateTokenTransferTransaction {
@@ -67,8 +432,133 @@ enum Command {
         from: AnchorPubkey,
         #[arg(long = "to")]
         to: AnchorPubkey,
-        #[arg(long = "amount")]
-        amount: f64,
+        /// Amount in UI units, e.g. 1_000.5 or 2.5m, offline also with the mint decimals, e.g.
+        /// 10@6, or a percentage of the source balance, e.g. 50%
+        #[arg(
+            long = "amount",
+            value_parser = parse_token_amount,
+            conflicts_with = "raw_amount",
+            required_unless_present = "raw_amount"
+        )]
+        amount: Option<Amount>,
+        /// Amount in base units, bypassing the conversion from UI units
+        #[arg(long = "raw-amount")]
+        raw_amount: Option<u64>,
+        /// Mint of the transferred token, or the symbol of a well-known mainnet token like USDC;
+        /// required in offline mode
+        #[arg(long = "mint", value_parser = tokens::parse_mint)]
+        mint: Option<tokens::MintArg>,
+        /// Transfer a Token-2022 token with transfer fees, paying the fee computed from the mint
+        #[arg(long = "with-transfer-fee", default_value_t = false)]
+        with_transfer_fee: bool,
+        /// Propose the transfer this many times, each as its own transaction, for load testing
+        /// outside mainnet; every proposal past the first gets a fresh nonce from --auto-nonce
+        #[arg(long = "count", hide = true, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        count: u32,
+        /// Treat --to as the owner wallet and transfer to its associated token account, creating
+        /// it in the proposal if it doesn't exist
+        #[arg(long = "allow-create-destination", default_value_t = false)]
+        allow_create_destination: bool,
+        /// Payer of the rent of a created destination account, signing the proposal
+        /// [default: the signer]
+        #[arg(long = "ata-payer", requires = "allow_create_destination")]
+        ata_payer: Option<AnchorPubkey>,
+    },
+    /// Create a transaction holding an arbitrary instruction
+    CreateRawTransaction {
+        #[command(flatten)]
+        signer: SignerArg,
+        #[arg(long = "multisig")]
+        multisig: AnchorPubkey,
+        /// Program invoked by the instruction
+        #[arg(long = "program-id")]
+        program_id: AnchorPubkey,
+        /// Instruction account as PUBKEY, or PUBKEY:w if writable; may be repeated
+        #[arg(long = "account", value_parser = parse_account_meta)]
+        accounts: Vec<SplAccountMeta>,
+        /// Instruction data in base64
+        #[arg(long = "data", default_value = "")]
+        data: String,
+        /// Index of the account signed by the multisig signer [default: every account equal to it]
+        #[arg(long = "pda-signer-index")]
+        pda_signer_index: Option<usize>,
+        /// Most accounts the instruction may have [default: what fits in the transaction account]
+        #[arg(long = "max-accounts")]
+        max_accounts: Option<usize>,
+    },
+    /// Create a transaction transferring SOL held by the multisig signer
+    CreateSolTransferTransaction {
+        #[command(flatten)]
+        signer: SignerArg,
+        #[arg(long = "multisig")]
+        multisig: AnchorPubkey,
+        #[arg(long = "to")]
+        to: AnchorPubkey,
+        /// Amount in SOL, e.g. 1_000.5 or 2.5k
+        #[arg(
+            long = "amount",
+            value_parser = parse_amount,
+            conflicts_with = "all",
+            required_unless_present = "all"
+        )]
+        amount: Option<f64>,
+        /// Transfer the whole balance of the multisig signer
+        #[arg(long = "all", default_value_t = false)]
+        all: bool,
+        /// Reduce the amount so that the multisig signer stays rent exempt
+        #[arg(long = "keep-rent-exempt", default_value_t = false)]
+        keep_rent_exempt: bool,
+    },
+    /// Create a transaction transferring the whole balance of a token account
+    CreateDrainTransaction {
+        #[command(flatten)]
+        signer: SignerArg,
+        #[arg(long = "multisig")]
+        multisig: AnchorPubkey,
+        #[arg(long = "from")]
+        from: AnchorPubkey,
+        #[arg(long = "to")]
+        to: AnchorPubkey,
+    },
+    /// Create a token transfer transaction for each payout listed in a CSV file
+    CreatePayoutTransactions {
+        #[command(flatten)]
+        signer: SignerArg,
+        #[arg(long = "multisig")]
+        multisig: AnchorPubkey,
+        #[arg(long = "from")]
+        from: AnchorPubkey,
+        /// CSV file of `RECIPIENT,AMOUNT[,MEMO]` rows: destination token account, amount in UI
+        /// units and a memo for the outer transaction, replacing --outer-memo; a header row and
+        /// lines starting with # are skipped
+        #[arg(long = "csv")]
+        csv: PathBuf,
+    },
+    /// Create a transaction letting a delegate spend tokens of a multisig token account
+    CreateApproveDelegateTransaction {
+        #[command(flatten)]
+        signer: SignerArg,
+        #[arg(long = "multisig")]
+        multisig: AnchorPubkey,
+        /// Token account owned by the multisig signer
+        #[arg(long = "account")]
+        account: AnchorPubkey,
+        #[arg(long = "delegate")]
+        delegate: AnchorPubkey,
+        /// Delegated amount in UI units, or a percentage of the balance such as `50%`
+        #[arg(long = "amount", value_parser = parse_token_amount)]
+        amount: Amount,
+    },
+    /// Create a transaction revoking the delegate of a multisig token account
+    CreateRevokeTransaction {
+        #[command(flatten)]
+        signer: SignerArg,
+        #[arg(long = "multisig")]
+        multisig: AnchorPubkey,
+        /// Token account owned by the multisig signer; offline it is taken for
+        /// a Token account, not a Token-2022 one
+        #[arg(long = "account")]
+        account: AnchorPubkey,
     },
     /// Approve a pending transaction
     Approve {
@@ -87,290 +577,5035 @@ enum Command {
         multisig: AnchorPubkey,
         #[arg(long = "transaction")]
         transaction: AnchorPubkey,
+        /// Simulate the execution and show the resulting token balances
+        #[arg(long = "simulate", default_value_t = false)]
+        simulate: bool,
+        /// Build the execution even if the transaction has already been executed
+        #[arg(long = "force", default_value_t = false)]
+        force: bool,
+        /// Refuse to execute unless the stored instruction invokes one of these programs,
+        /// comma-separated
+        #[arg(long = "allowed-programs", value_delimiter = ',')]
+        allowed_programs: Vec<AnchorPubkey>,
+        #[command(flatten)]
+        compute_budget: ComputeBudgetArgs,
+    },
+    /// Create the approval messages a transaction still needs, followed by its execution
+    ApproveAndExecute {
+        /// Signer of the execution
+        #[command(flatten)]
+        signer: SignerArg,
+        #[arg(long = "multisig")]
+        multisig: AnchorPubkey,
+        #[arg(long = "transaction")]
+        transaction: AnchorPubkey,
+        /// Owner able to approve, as SIGNER:NONCE_ACCOUNT:NONCE; may be repeated
+        #[arg(long = "approver", value_parser = parse_signer)]
+        approvers: Vec<SignerArg>,
+        /// Print the messages as one JSON bundle, to be signed and sent atomically with SubmitBundle
+        #[arg(long = "as-bundle", default_value_t = false)]
+        as_bundle: bool,
+        /// Lamports the executing signer tips the block engine with, which only lands bundles
+        /// paying a tip
+        #[arg(long = "tip", requires = "as_bundle")]
+        tip: Option<u64>,
+        /// Refuse to execute unless the stored instruction invokes one of these programs,
+        /// comma-separated
+        #[arg(long = "allowed-programs", value_delimiter = ',')]
+        allowed_programs: Vec<AnchorPubkey>,
+        #[command(flatten)]
+        compute_budget: ComputeBudgetArgs,
+    },
+    /// Approve a transaction with several locally held owner keys, printing the signed approvals as JSON
+    ApproveWithKeys {
+        #[arg(long = "multisig")]
+        multisig: AnchorPubkey,
+        #[arg(long = "transaction")]
+        transaction: AnchorPubkey,
+        /// Owner key file and its nonce account, as KEY_FILE:NONCE_ACCOUNT; may be repeated
+        #[arg(long = "owner-key", value_parser = parse_owner_key, required = true)]
+        owner_keys: Vec<(String, AnchorPubkey)>,
     },
     /// Submit a signed transaction
     Submit {
-        #[arg(long = "transaction")]
-        transaction: String,
+        /// Message, or a transaction holding its signatures as printed by
+        /// `solana-signer --assemble`
+        #[arg(long = "transaction", required_unless_present_any = ["from_session", "from_qr"])]
+        transaction: Option<String>,
+        /// File with the scanned QR chunks of the message or transaction, one per line, or - for
+        /// stdin
+        #[arg(long = "from-qr", conflicts_with_all = ["transaction", "from_session"])]
+        from_qr: Option<PathBuf>,
+        /// Signatures of the message, in the order of its signers
         #[arg(long = "signatures")]
         signatures: Vec<Signature>,
+        /// Signing session holding every signature, instead of --transaction and --signatures
+        #[arg(long = "from-session", conflicts_with_all = ["transaction", "signatures"])]
+        from_session: Option<PathBuf>,
+        /// Directory of JSON files holding a signature each, as {"signer": ..., "signature": ...},
+        /// instead of --signatures
+        #[arg(long = "signature-dir", conflicts_with_all = ["signatures", "from_session"])]
+        signature_dir: Option<PathBuf>,
+        /// Submit even if the durable nonce has advanced or the recent blockhash expired
+        #[arg(long = "force", default_value_t = false)]
+        force: bool,
+        /// Refuse to submit unless the finalized nonce still equals the one of the transaction
+        #[arg(
+            long = "require-confirmed-nonce",
+            default_value_t = false,
+            conflicts_with = "force"
+        )]
+        require_confirmed_nonce: bool,
+        /// Print the program logs of the fee payer's transactions while waiting for the confirmation
+        #[arg(long = "stream-logs", default_value_t = false)]
+        stream_logs: bool,
+        /// Send without checking the signatures locally first
+        #[arg(long = "skip-verify", default_value_t = false)]
+        skip_verify: bool,
+        /// Commitment of this command, overriding the global --commitment
+        #[arg(long = "commitment", value_enum)]
+        commitment: Option<Commitment>,
+        /// Wait until the transaction is this many confirmations deep, up to the 32 of a
+        /// finalized one
+        #[arg(long = "min-confirmations", value_parser = clap::value_parser!(u64).range(1..=32))]
+        min_confirmations: Option<u64>,
+    },
+    /// Add signatures to a signing session, each in the slot of the signer it verifies for
+    AddSignatures {
+        #[arg(long = "file")]
+        file: PathBuf,
+        #[arg(long = "signature", required = true)]
+        signatures: Vec<Signature>,
+    },
+    /// Send signed transactions as an atomic bundle to a block engine
+    SubmitBundle {
+        /// JSON bundle as printed by --as-bundle, with the signatures filled in
+        #[arg(long = "file")]
+        file: PathBuf,
+        /// Bundle endpoint of the block engine [default: the Jito block engine on mainnet,
+        /// required on other clusters]
+        #[arg(long = "endpoint")]
+        endpoint: Option<String>,
+    },
+    /// Submit several independent signed transactions, continuing past failures
+    SubmitBatch {
+        /// JSON list of transactions with their signatures, as printed by --as-bundle
+        #[arg(long = "file")]
+        file: PathBuf,
+        /// Number of transactions in flight at once
+        #[arg(long = "concurrency", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        concurrency: u32,
+    },
+    /// Fetch the current nonces of several signers at once, as SIGNER:NONCE_ACCOUNT:NONCE
+    PrepareNonces {
+        /// Signer and its nonce account, as SIGNER:NONCE_ACCOUNT; may be repeated
+        #[arg(long = "signer", value_parser = parse_nonce_pair, required = true)]
+        signers: Vec<(AnchorPubkey, AnchorPubkey)>,
+        #[arg(long = "json", default_value_t = false)]
+        json: bool,
+    },
+    /// Close a nonce account, withdrawing its whole balance
+    CloseNonceAccount {
+        #[command(flatten)]
+        signer: SignerArg,
+        /// Nonce account to close
+        #[arg(long = "account")]
+        account: AnchorPubkey,
+        /// Authority of the closed nonce account [default: the signer]
+        #[arg(long = "authority")]
+        authority: Option<AnchorPubkey>,
+        #[arg(long = "destination")]
+        destination: AnchorPubkey,
+        /// Balance of the closed nonce account, required in offline mode
+        #[arg(long = "lamports")]
+        lamports: Option<u64>,
+    },
+    /// Hand the authority of a nonce account over to another key
+    SetNonceAuthority {
+        #[command(flatten)]
+        signer: SignerArg,
+        /// Nonce account whose authority changes
+        #[arg(long = "account")]
+        account: AnchorPubkey,
+        /// Current authority of the nonce account [default: the signer]
+        #[arg(long = "authority")]
+        authority: Option<AnchorPubkey>,
+        #[arg(long = "new-authority")]
+        new_authority: AnchorPubkey,
+    },
+    /// Show the full contents of a transaction account
+    ShowTransaction {
+        #[arg(long = "transaction")]
+        transaction: AnchorPubkey,
+        /// Commitment of this command, overriding the global --commitment
+        #[arg(long = "commitment", value_enum)]
+        commitment: Option<Commitment>,
+        /// Anchor IDL JSON file decoding the arguments of a multisig instruction by name
+        #[arg(long = "idl-file")]
+        idl_file: Option<String>,
+    },
+    /// List the transactions proposed to a multisig, ordered by address
+    ListTransactions {
+        #[arg(long = "multisig")]
+        multisig: AnchorPubkey,
+        /// Only list transactions created in or after this slot
+        #[arg(long = "since-slot")]
+        since_slot: Option<u64>,
+        /// List at most this many transactions
+        #[arg(long = "limit")]
+        limit: Option<usize>,
+    },
+    /// List the token accounts owned by the multisig signer
+    ListAssets {
+        #[arg(long = "multisig")]
+        multisig: AnchorPubkey,
+        /// Only list accounts of this mint, or the symbol of a well-known mainnet token like USDC
+        #[arg(long = "mint", value_parser = tokens::parse_mint)]
+        mint: Option<tokens::MintArg>,
+        /// Only list accounts holding at least this many tokens, in UI units
+        #[arg(long = "min-balance", value_parser = parse_amount)]
+        min_balance: Option<f64>,
+    },
+    /// Check that a submitted execution succeeded and marked the transaction as executed
+    VerifyExecution {
+        /// Signature of the submitted execution
+        #[arg(long = "signature")]
+        signature: Signature,
+        #[arg(long = "transaction")]
+        transaction: AnchorPubkey,
+        /// Commitment of this command, overriding the global --commitment
+        #[arg(long = "commitment", value_enum)]
+        commitment: Option<Commitment>,
+    },
+    /// Check that a proposal can still be executed by the current owners before collecting
+    /// approvals for it
+    ValidateProposal {
+        #[arg(long = "transaction")]
+        transaction: AnchorPubkey,
+    },
+    /// Show how many owners approved a transaction
+    TransactionStatus {
+        #[arg(long = "transaction")]
+        transaction: AnchorPubkey,
+        #[arg(long = "json", default_value_t = false)]
+        json: bool,
+        /// Commitment of this command, overriding the global --commitment
+        #[arg(long = "commitment", value_enum)]
+        commitment: Option<Commitment>,
+    },
+    /// Sign an off-chain message, such as an approval attestation, with the local key
+    SignOffchain {
+        #[arg(long = "message")]
+        message: String,
+        /// Application domain prefixed to the message, so the signature can't be used in
+        /// another context
+        #[arg(long = "domain", value_parser = parse_domain)]
+        domain: Option<String>,
+    },
+    /// Verify a signature of an off-chain message
+    VerifyOffchain {
+        #[arg(long = "signer")]
+        signer: AnchorPubkey,
+        #[arg(long = "message")]
+        message: String,
+        #[arg(long = "signature")]
+        signature: Signature,
+        /// Domain the message must have been signed over
+        #[arg(long = "domain", value_parser = parse_domain)]
+        domain: Option<String>,
+    },
+    /// Write an attestation of a proposal: its decoded contents, signed off-chain by the local
+    /// key as the proposer
+    AttestProposal {
+        #[arg(long = "transaction")]
+        transaction: AnchorPubkey,
+        /// File to write the attestation to [default: attestation-<TRANSACTION>.json]
+        #[arg(long = "output")]
+        output: Option<PathBuf>,
+    },
+    /// Verify the signature of a proposal attestation and, online, that it matches the proposal
+    VerifyAttestation {
+        #[arg(long = "file")]
+        file: PathBuf,
+    },
+    /// Describe the instructions of a transaction message
+    Decode {
+        #[arg(long = "transaction", required_unless_present = "from_qr")]
+        transaction: Option<String>,
+        /// File with the scanned QR chunks of the message, one per line, or - for stdin
+        #[arg(long = "from-qr", conflicts_with = "transaction")]
+        from_qr: Option<PathBuf>,
+        /// Print the instructions as JSON, decoding the multisig instruction
+        /// arguments with the IDL the program published on-chain
+        #[arg(long = "idl")]
+        idl: bool,
+        /// Like --idl, reading the IDL from a local JSON file instead of the chain
+        #[arg(long = "idl-file", conflicts_with = "idl")]
+        idl_file: Option<String>,
+    },
+    /// Dump every field of a message as serialized, with the raw account indices
+    DumpMessageFields {
+        #[arg(long = "transaction")]
+        transaction: String,
+    },
+    /// Rebuild the message of a signing request manifest from its listed instructions and check
+    /// that the distributed message matches it byte for byte
+    RebuildMessage {
+        #[arg(long = "manifest")]
+        manifest: PathBuf,
+        /// Distributed message to check [default: the one in the manifest]
+        #[arg(long = "transaction")]
+        transaction: Option<String>,
+    },
+    /// Show the differences between two base64 transaction messages
+    Compare {
+        /// Original message, or @FILE containing it
+        #[arg(long = "old")]
+        old: String,
+        /// Regenerated message, or @FILE containing it
+        #[arg(long = "new")]
+        new: String,
+    },
+    /// Check that the deployed multisig program is the audited build, by the SHA-256 of its bytecode
+    VerifyProgram {
+        /// Expected hex SHA-256 of the bytecode without trailing zero padding, as by `solana-verify`
+        #[arg(long = "expect-hash")]
+        expect_hash: Option<String>,
+    },
+    /// Generate a new keypair for a signer or a nonce authority
+    GenerateKeypair {
+        /// Key file to write, as a JSON array like solana-keygen
+        #[arg(long = "outfile", required_unless_present = "no_outfile")]
+        outfile: Option<PathBuf>,
+        /// Print the base58 secret key instead of writing a file, for ephemeral keys
+        #[arg(
+            long = "no-outfile",
+            default_value_t = false,
+            conflicts_with = "outfile"
+        )]
+        no_outfile: bool,
+        /// Overwrite an existing key file
+        #[arg(long = "force", default_value_t = false)]
+        force: bool,
+    },
+    /// Write a review document of a base64 message for the approvers: its instructions and
+    /// accounts, signers, nonce and checksum
+    ExportReview {
+        /// Message, or @FILE containing it
+        #[arg(long = "transaction")]
+        transaction: String,
+        #[arg(long = "format", value_enum, default_value_t = review::ReviewFormat::Markdown)]
+        format: review::ReviewFormat,
+        /// File to write the review to [default: stdout]
+        #[arg(long = "output")]
+        output: Option<PathBuf>,
+    },
+    /// Show who proposes, approves and executes an operation, and the nonces and fees each
+    /// step needs
+    Plan {
+        #[arg(long = "multisig")]
+        multisig: AnchorPubkey,
+        #[arg(long = "action", value_enum)]
+        action: plan::Action,
+        /// Owner proposing the operation [default: any owner]
+        #[arg(long = "proposer")]
+        proposer: Option<AnchorPubkey>,
+    },
+    /// Estimate the balance needed to make an account rent exempt
+    Rent {
+        /// Account size in bytes
+        #[arg(long = "size")]
+        size: Option<usize>,
+    },
+    /// Run the whole multisig flow against a local validator with throwaway keys
+    #[command(hide(true))]
+    SelfTest,
+    /// Print reproducible keypairs, multisig and transaction accounts as JSON, for tests
+    #[command(hide(true))]
+    Fixture {
+        /// Seed the fixture is derived from
+        #[arg(long = "seed", default_value_t = 0)]
+        seed: u64,
     },
 }
 
-fn build_tx(
-    payer: AnchorPubkey,
-    nonce: hash::Hash,
-    nonce_authority: AnchorPubkey,
-    instructions: Vec<Instruction>,
-) -> anyhow::Result<Message> {
-    let mut message = Message::new_with_nonce(instructions, Some(&payer), &nonce_authority, &payer);
-    message.recent_blockhash = nonce;
-    println!("You may now check the transaction using external tools.\nHere is the transaction data in base64:\n\n{}\n",
-        BASE64_STANDARD.encode(message.serialize())
-    );
-
-    Ok(message)
-}
+impl Command {
+    /// Multisig the command operates on, if it takes one.
+    fn multisig(&self) -> Option<AnchorPubkey> {
+        match self {
+            Command::CreateTokenTransferTransaction { multisig, .. }
+            | Command::CreateRawTransaction { multisig, .. }
+            | Command::CreateSolTransferTransaction { multisig, .. }
+            | Command::CreateDrainTransaction { multisig, .. }
+            | Command::CreatePayoutTransactions { multisig, .. }
+            | Command::CreateApproveDelegateTransaction { multisig, .. }
+            | Command::CreateRevokeTransaction { multisig, .. }
+            | Command::Approve { multisig, .. }
+            | Command::ExecuteTokenTransferTransaction { multisig, .. }
+            | Command::ApproveAndExecute { multisig, .. }
+            | Command::ApproveWithKeys { multisig, .. }
+            | Command::Plan { multisig, .. }
+            | Command::ListAssets { multisig, .. } => Some(*multisig),
+            _ => None,
+        }
+    }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
-    let dummy_signer = Keypair::new();
-    let client =
-        Client::new_with_options(cli.cluster, &dummy_signer, CommitmentConfig::processed());
+    /// Accounts the command expects to exist on the cluster, with their roles.
+    fn existing_accounts(&self) -> Vec<(&'static str, AnchorPubkey)> {
+        let mut accounts: Vec<_> = self
+            .multisig()
+            .map(|multisig| ("multisig", multisig))
+            .into_iter()
+            .collect();
+        match self {
+            Command::CreateTokenTransferTransaction {
+                from,
+                allow_create_destination: true,
+                ..
+            } => {
+                // The destination is a wallet, which may not hold any SOL.
+                accounts.push(("source token account", *from));
+            }
+            Command::CreatePayoutTransactions { from, .. } => {
+                accounts.push(("source token account", *from));
+            }
+            Command::CreateTokenTransferTransaction { from, to, .. }
+            | Command::CreateDrainTransaction { from, to, .. } => {
+                accounts.push(("source token account", *from));
+                accounts.push(("destination token account", *to));
+            }
+            Command::CreateApproveDelegateTransaction { account, .. }
+            | Command::CreateRevokeTransaction { account, .. } => {
+                accounts.push(("token account", *account));
+            }
+            Command::Approve { transaction, .. }
+            | Command::ExecuteTokenTransferTransaction { transaction, .. }
+            | Command::ApproveAndExecute { transaction, .. }
+            | Command::ApproveWithKeys { transaction, .. }
+            | Command::ShowTransaction { transaction, .. }
+            | Command::ValidateProposal { transaction }
+            | Command::AttestProposal { transaction, .. }
+            | Command::VerifyExecution { transaction, .. }
+            | Command::TransactionStatus { transaction, .. } => {
+                accounts.push(("transaction", *transaction));
+            }
+            _ => {}
+        }
+        accounts
+    }
 
-    // Program instance
-    let program = client.program(cli.pid)?;
+    /// Signer paying for and authorizing the transaction the command builds.
+    fn signer_mut(&mut self) -> Option<&mut SignerArg> {
+        match self {
+            Command::CreateMultisig { signer, .. }
+            | Command::CreateTokenTransferTransaction { signer, .. }
+            | Command::CreateRawTransaction { signer, .. }
+            | Command::CreateSolTransferTransaction { signer, .. }
+            | Command::CreateDrainTransaction { signer, .. }
+            | Command::CreatePayoutTransactions { signer, .. }
+            | Command::CreateApproveDelegateTransaction { signer, .. }
+            | Command::CreateRevokeTransaction { signer, .. }
+            | Command::Approve { signer, .. }
+            | Command::ExecuteTokenTransferTransaction { signer, .. }
+            | Command::ApproveAndExecute { signer, .. }
+            | Command::CloseNonceAccount { signer, .. }
+            | Command::SetNonceAuthority { signer, .. } => Some(signer),
+            _ => None,
+        }
+    }
 
-    match cli.command {
-        Command::CreateMultisig {
-            signer,
-            signers,
-            threshold,
-        } => {
-            let keypair = Keypair::new();
-            let accounts = multisig_accounts::CreateMultisig {
-                multisig: keypair.pubkey(),
-            };
-            let (multisig_pda, nonce) = derive_multisig_signer(&keypair.pubkey(), &cli.pid);
-            let instructions = multisig_instructions::CreateMultisig {
-                owners: signers,
-                threshold,
-                nonce,
-            };
-            let req = program
-                .request()
-                .accounts(accounts)
-                .accounts(AccountMeta::new_readonly(sysvar::rent::id(), false))
-                .args(instructions)
-                .instruction(system_instruction::create_account(
-                    &signer.signer,
-                    &keypair.pubkey(),
-                    program.rpc().get_minimum_balance_for_rent_exemption(500)?,
-                    500,
-                    &program.id(),
-                ));
+    /// Whether the command builds the outer transaction creating or executing a proposal.
+    fn proposes_or_executes(&self) -> bool {
+        matches!(
+            self,
+            Command::CreateTokenTransferTransaction { .. }
+                | Command::CreateRawTransaction { .. }
+                | Command::CreateSolTransferTransaction { .. }
+                | Command::CreateDrainTransaction { .. }
+                | Command::CreatePayoutTransactions { .. }
+                | Command::CreateApproveDelegateTransaction { .. }
+                | Command::CreateRevokeTransaction { .. }
+                | Command::ExecuteTokenTransferTransaction { .. }
+                | Command::ApproveAndExecute { .. }
+        )
+    }
 
-            let tx = build_tx(
-                signer.signer,
-                signer.nonce,
-                signer.nonce_account,
-                req.instructions()?,
-            )?;
-            let sig = keypair.sign_message(&tx.serialize());
+    /// Whether the command creates an account, paid by the signer or the rent payer.
+    fn creates_account(&self) -> bool {
+        matches!(
+            self,
+            Command::CreateMultisig { .. }
+                | Command::CreateTokenTransferTransaction { .. }
+                | Command::CreateRawTransaction { .. }
+                | Command::CreateSolTransferTransaction { .. }
+                | Command::CreateDrainTransaction { .. }
+                | Command::CreatePayoutTransactions { .. }
+                | Command::CreateApproveDelegateTransaction { .. }
+                | Command::CreateRevokeTransaction { .. }
+        )
+    }
 
-            println!("Transaction signed by multisig account: {}", sig);
-            println!("Multisig address: {}", keypair.pubkey());
-            println!("Multisig PDA: {}", multisig_pda);
+    /// Commitment the command overrides the global one with.
+    fn commitment(&self) -> Option<Commitment> {
+        match self {
+            Command::Submit { commitment, .. }
+            | Command::ShowTransaction { commitment, .. }
+            | Command::VerifyExecution { commitment, .. }
+            | Command::TransactionStatus { commitment, .. } => *commitment,
+            _ => None,
         }
-        Command::CreateTokenTransferTransaction {
-            signer,
-            multisig,
-            from,
-            to,
-            amount,
-        } => {
-            println!(
-                "{}",
-                "Preparing a token transfer transaction with the following parameters:".bold()
-            );
-            println!(
-                "Multisig address: {}\nFrom address: {}\nTo address: {}\nAmount: {}\n",
-                style(multisig).green(),
-                style(from).green(),
-                style(to).green(),
-                style(amount).green(),
-            );
+    }
 
-            let from_account = program
-                .async_rpc()
-                .get_token_account(&from)
-                .await?
-                .ok_or(anyhow::Error::msg("source token account not found"))?;
-            let to_account = program
-                .async_rpc()
-                .get_token_account(&to)
-                .await?
-                .ok_or(anyhow::Error::msg("destination token account not found"))?;
-            if from_account.mint != to_account.mint {
-                return Err(anyhow::Error::msg(
-                    "source and destination accounts have different mint addresses",
-                ));
+    /// Whether the command signs or submits a transaction by itself. Listed
+    /// are the commands that don't, so a new command asks for the mainnet
+    /// confirmation until it is added here.
+    fn signs(&self) -> bool {
+        !matches!(
+            self,
+            Command::AddSignatures { .. }
+                | Command::PrepareNonces { .. }
+                | Command::ShowTransaction { .. }
+                | Command::ListTransactions { .. }
+                | Command::ListAssets { .. }
+                | Command::VerifyExecution { .. }
+                | Command::ValidateProposal { .. }
+                | Command::TransactionStatus { .. }
+                | Command::SignOffchain { .. }
+                | Command::VerifyOffchain { .. }
+                | Command::AttestProposal { .. }
+                | Command::VerifyAttestation { .. }
+                | Command::Decode { .. }
+                | Command::DumpMessageFields { .. }
+                | Command::RebuildMessage { .. }
+                | Command::Compare { .. }
+                | Command::VerifyProgram { .. }
+                | Command::GenerateKeypair { .. }
+                | Command::ExportReview { .. }
+                | Command::Plan { .. }
+                | Command::Rent { .. }
+                | Command::Fixture { .. }
+        )
+    }
+}
+
+/// Transfer amount as given on the command line.
+#[derive(Clone, Copy)]
+enum Amount {
+    Ui(f64),
+    /// Amount in UI units of a mint with the given decimals, for offline use.
+    UiAt {
+        amount: f64,
+        decimals: u8,
+    },
+    Raw(u64),
+    /// Share of the source balance, as the exact fraction `numerator / denominator`.
+    Share {
+        numerator: u128,
+        denominator: u128,
+    },
+}
+
+impl Amount {
+    /// Converts the amount to base units, `balance` being the source balance
+    /// if it is known.
+    fn to_raw(self, decimals: u8, balance: Option<u64>) -> anyhow::Result<u64> {
+        match self {
+            Amount::Ui(amount) => Ok(spl_token::ui_amount_to_amount(amount, decimals)),
+            Amount::UiAt {
+                amount,
+                decimals: given,
+            } => {
+                if given != decimals {
+                    return Err(SerumError::Validation(format!(
+                        "the amount is given with {} decimals, but the mint has {}",
+                        given, decimals
+                    ))
+                    .into());
+                }
+                Ok(spl_token::ui_amount_to_amount(amount, decimals))
             }
-            if from_account.token_amount.ui_amount.is_none()
-                || from_account.token_amount.ui_amount.unwrap() < amount
-            {
-                return Err(anyhow::Error::msg(
-                    "source account doesn't have sufficient amount of token",
-                ));
+            Amount::Raw(amount) => Ok(amount),
+            Amount::Share {
+                numerator,
+                denominator,
+            } => {
+                let balance = balance.ok_or_else(|| {
+                    SerumError::Validation(
+                        "a percentage of the balance can't be resolved offline".to_string(),
+                    )
+                })?;
+                // Rounds down, never exceeding the share.
+                Ok((balance as u128 * numerator / denominator) as u64)
             }
+        }
+    }
 
-            let amount = spl_token::ui_amount_to_amount(amount, from_account.token_amount.decimals);
+    /// Decimals given along with the amount.
+    fn decimals(self) -> Option<u8> {
+        match self {
+            Amount::UiAt { decimals, .. } => Some(decimals),
+            _ => None,
+        }
+    }
 
-            let keypair = Keypair::new();
-            let (multisig_pda, _) = derive_multisig_signer(&multisig, &cli.pid);
-            let transfer = token_instruction::transfer(
-                &spl_token::id(),
-                &from.to_bytes().into(),
-                &to.to_bytes().into(),
-                &multisig_pda.to_bytes().into(),
-                &[],
-                amount,
-            )?;
+    /// Rejects decimals given along with the amount, which online would
+    /// compete with the decimals of the mint.
+    fn check_online(self) -> anyhow::Result<()> {
+        if self.decimals().is_some() {
+            return Err(SerumError::Validation(
+                "AMOUNT@DECIMALS is only accepted offline, online the decimals are read from the mint"
+                    .to_string(),
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
 
-            let transfer_accounts: Vec<TransactionAccount> =
-                transfer.accounts.iter().map(Into::into).collect();
-            let accounts = multisig_accounts::CreateTransaction {
-                multisig,
-                transaction: keypair.pubkey(),
-                proposer: signer.signer,
-            };
-            let instructions = multisig_instructions::CreateTransaction {
-                pid: spl_token::id().to_bytes().into(),
-                accs: transfer_accounts,
-                data: transfer.data,
+/// Mint decimals known during this invocation, keyed by mint address. Mints
+/// known here aren't fetched again by `fetch_token_accounts`.
+struct MintDecimals(HashMap<AnchorPubkey, u8>);
+
+impl MintDecimals {
+    fn new(prefilled: &[(AnchorPubkey, u8)]) -> Self {
+        Self(prefilled.iter().copied().collect())
+    }
+
+    fn get(&self, mint: &AnchorPubkey) -> Option<u8> {
+        self.0.get(mint).copied()
+    }
+
+    /// Records decimals reported by the cluster, rejecting ones that contradict a known value.
+    fn observe(&mut self, mint: AnchorPubkey, decimals: u8) -> anyhow::Result<u8> {
+        let known = *self.0.entry(mint).or_insert(decimals);
+        if known != decimals {
+            return Err(anyhow::Error::msg(format!(
+                "mint {} has {} decimals, but {} were given",
+                mint, decimals, known
+            )));
+        }
+        Ok(decimals)
+    }
+}
+
+fn parse_signer(s: &str) -> anyhow::Result<SignerArg> {
+    match s.split(':').collect::<Vec<_>>()[..] {
+        [signer, nonce_account, nonce] => Ok(SignerArg::new(
+            signer.parse()?,
+            nonce_account.parse()?,
+            nonce.parse()?,
+        )),
+        _ => Err(anyhow::Error::msg("expected SIGNER:NONCE_ACCOUNT:NONCE")),
+    }
+}
+
+/// Parses a token amount, either as by `parse_amount`, optionally followed
+/// by `@DECIMALS` such as `10@6`, or as a percentage of the source balance
+/// such as `50%` or `12.5%`.
+fn parse_token_amount(s: &str) -> anyhow::Result<Amount> {
+    if let Some((amount, decimals)) = s.split_once('@') {
+        let decimals = decimals
+            .parse()
+            .map_err(|_| anyhow::Error::msg(format!("malformed decimals {:?}", decimals)))?;
+        return Ok(Amount::UiAt {
+            amount: parse_amount(amount)?,
+            decimals,
+        });
+    }
+    let Some(percent) = s.strip_suffix('%') else {
+        return Ok(Amount::Ui(parse_amount(s)?));
+    };
+    let malformed = || anyhow::Error::msg(format!("malformed percentage {:?}", s));
+    let (whole, fraction) = percent.split_once('.').unwrap_or((percent, ""));
+    if whole.is_empty()
+        || fraction.len() > 9
+        || (percent.contains('.') && fraction.is_empty())
+        || !whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return Err(malformed());
+    }
+    let numerator: u128 = format!("{}{}", whole, fraction)
+        .parse()
+        .map_err(|_| malformed())?;
+    let denominator = 100 * 10u128.pow(fraction.len() as u32);
+    if numerator == 0 || numerator > denominator {
+        return Err(anyhow::Error::msg(
+            "the percentage must be above 0% and at most 100%",
+        ));
+    }
+    Ok(Amount::Share {
+        numerator,
+        denominator,
+    })
+}
+
+/// Parses a decimal amount that may group digits with underscores and end
+/// with a `k`, `m` or `b` multiplier, e.g. `1_000_000` or `1.5m`.
+fn parse_amount(s: &str) -> anyhow::Result<f64> {
+    let (number, multiplier) = match s.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&s[..i], 1e3),
+        Some((i, 'm' | 'M')) => (&s[..i], 1e6),
+        Some((i, 'b' | 'B')) => (&s[..i], 1e9),
+        _ => (s, 1.0),
+    };
+    let malformed = || anyhow::Error::msg(format!("malformed amount {:?}", s));
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    for part in [whole, fraction] {
+        // Underscores may only separate digits.
+        if part.starts_with('_')
+            || part.ends_with('_')
+            || part.contains("__")
+            || !part.chars().all(|c| c.is_ascii_digit() || c == '_')
+        {
+            return Err(malformed());
+        }
+    }
+    if whole.is_empty() || (number.contains('.') && fraction.is_empty()) {
+        return Err(malformed());
+    }
+    let amount: f64 = number.replace('_', "").parse().map_err(|_| malformed())?;
+    Ok(amount * multiplier)
+}
+
+fn parse_account_meta(s: &str) -> anyhow::Result<SplAccountMeta> {
+    let (pubkey, is_writable) = match s.split_once(':') {
+        Some((pubkey, "w")) => (pubkey, true),
+        Some(_) => return Err(anyhow::Error::msg("expected PUBKEY or PUBKEY:w")),
+        None => (s, false),
+    };
+    Ok(SplAccountMeta {
+        pubkey: pubkey.parse()?,
+        is_signer: false,
+        is_writable,
+    })
+}
+
+fn parse_owner_key(s: &str) -> anyhow::Result<(String, AnchorPubkey)> {
+    let (key_file, nonce_account) = s
+        .rsplit_once(':')
+        .ok_or(anyhow::Error::msg("expected KEY_FILE:NONCE_ACCOUNT"))?;
+    Ok((key_file.to_string(), nonce_account.parse()?))
+}
+
+fn parse_nonce_pair(s: &str) -> anyhow::Result<(AnchorPubkey, AnchorPubkey)> {
+    let (signer, nonce_account) = s
+        .split_once(':')
+        .ok_or(anyhow::Error::msg("expected SIGNER:NONCE_ACCOUNT"))?;
+    Ok((signer.parse()?, nonce_account.parse()?))
+}
+
+fn parse_mint_decimals(s: &str) -> anyhow::Result<(AnchorPubkey, u8)> {
+    let (mint, decimals) = s
+        .split_once('=')
+        .ok_or(anyhow::Error::msg("expected MINT=DECIMALS"))?;
+    Ok((mint.parse()?, decimals.parse()?))
+}
+
+async fn rent_exemption(
+    program: &Program<&Keypair>,
+    offline: bool,
+    size: usize,
+) -> anyhow::Result<u64> {
+    if offline {
+        return Ok(Rent::default().minimum_balance(size));
+    }
+    log::info!("requesting rent exemption for {} bytes", size);
+    let rpc = program.async_rpc();
+    rpc::retry(|| rpc.get_minimum_balance_for_rent_exemption(size)).await
+}
+
+/// Returns the nonce account advanced by a durable-nonce message.
+fn durable_nonce_account(message: &Message) -> Option<AnchorPubkey> {
+    let ix = message.instructions.first()?;
+    let program_id = message.account_keys.get(ix.program_id_index as usize)?;
+    if !system_program::check_id(program_id) {
+        return None;
+    }
+    match bincode::deserialize(&ix.data) {
+        Ok(SystemInstruction::AdvanceNonceAccount) => {}
+        _ => return None,
+    }
+    message
+        .account_keys
+        .get(*ix.accounts.first()? as usize)
+        .copied()
+}
+
+/// Authority of the durable nonce the message advances, if it does.
+fn durable_nonce_authority(message: &Message) -> Option<AnchorPubkey> {
+    durable_nonce_account(message)?;
+    // AdvanceNonceAccount takes the nonce account, the recent blockhashes
+    // sysvar and the authority.
+    let index = *message.instructions[0].accounts.get(2)?;
+    message.account_keys.get(index as usize).copied()
+}
+
+/// Required signers of the message, in the order of their signatures, with
+/// their roles.
+fn required_signers(message: &Message) -> Vec<(AnchorPubkey, Vec<&'static str>)> {
+    let nonce_authority = durable_nonce_authority(message);
+    message.account_keys[..message.header.num_required_signatures as usize]
+        .iter()
+        .enumerate()
+        .map(|(i, key)| {
+            let mut roles = vec!["signer"];
+            if i == 0 {
+                roles.push("fee payer");
+            }
+            if nonce_authority == Some(*key) {
+                roles.push("nonce authority");
+            }
+            (*key, roles)
+        })
+        .collect()
+}
+
+/// Checks that the signer is able to advance its nonce, see `SignerArg`.
+async fn check_nonce_authority(
+    program: &Program<&Keypair>,
+    signer: &SignerArg,
+) -> anyhow::Result<()> {
+    if !signer.signer.is_on_curve() {
+        return Err(SerumError::Validation(format!(
+            "the signer {} is a program-derived address, which can only sign through its program and can't pay for or sign a transaction",
+            signer.signer
+        ))
+        .into());
+    }
+    let nonce_account = signer.nonce_account()?;
+    log::info!("fetching nonce account {}", nonce_account);
+    let rpc = program.async_rpc();
+    let account =
+        rpc::retry(|| nonce_utils::nonblocking::get_account(&rpc, &nonce_account)).await?;
+    let authority = nonce_utils::nonblocking::data_from_account(&account)?.authority;
+    if authority == signer.signer {
+        return Ok(());
+    }
+    let message = if authority.is_on_curve() {
+        format!(
+            "nonce account {} is authorized by {}, not by the signer {}",
+            nonce_account, authority, signer.signer
+        )
+    } else {
+        format!(
+            "nonce account {} is authorized by the program-derived address {}, which can't advance it: the advance is the first instruction of the transaction and no program can sign it; use a nonce account authorized by the signer",
+            nonce_account, authority
+        )
+    };
+    Err(SerumError::Validation(message).into())
+}
+
+async fn current_nonce(
+    program: &Program<&Keypair>,
+    nonce_account: &AnchorPubkey,
+) -> anyhow::Result<hash::Hash> {
+    log::info!("fetching nonce account {}", nonce_account);
+    let account =
+        nonce_utils::nonblocking::get_account(&program.async_rpc(), nonce_account).await?;
+    Ok(nonce_utils::nonblocking::data_from_account(&account)?.blockhash())
+}
+
+/// Creates a nonce account authorized by `signer`, paid by the local key, and
+/// points `signer` at its nonce. The account is initialized with a durable
+/// nonce, so it needs no advance by its authority before the first use.
+async fn create_auto_nonce(
+    program: &Program<&Keypair>,
+    output: &Output,
+    signer: &mut SignerArg,
+) -> anyhow::Result<()> {
+    let payer = output.local_signer.as_ref().ok_or(anyhow::Error::msg(
+        "--auto-nonce needs --private-key to pay for the nonce account",
+    ))?;
+    let rpc = program.async_rpc();
+    let nonce = Keypair::new();
+    let lamports =
+        rpc::retry(|| rpc.get_minimum_balance_for_rent_exemption(NonceState::size())).await?;
+    let instructions = system_instruction::create_nonce_account(
+        &payer.pubkey(),
+        &nonce.pubkey(),
+        &signer.signer,
+        lamports,
+    );
+    let blockhash = rpc::retry(|| rpc.get_latest_blockhash()).await?;
+    let tx = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer.pubkey()),
+        &[payer, &nonce],
+        blockhash,
+    );
+    log::info!("creating nonce account {}", nonce.pubkey());
+    let sig = rpc.send_and_confirm_transaction(&tx).await?;
+    let key_file = format!("nonce-{}.json", nonce.pubkey());
+    write_keypair_file(&nonce, &key_file)
+        .map_err(|e| anyhow::Error::msg(format!("failed to write {}: {}", key_file, e)))?;
+    println!(
+        "Nonce account {} created in {}, keypair written to {}",
+        style(nonce.pubkey()).green(),
+        sig,
+        key_file
+    );
+    warn(&format!(
+        "the nonce account holds {}, close it with CloseNonceAccount once the transaction is submitted",
+        decode::format_lamports(lamports)
+    ));
+    signer.nonce = Some(current_nonce(program, &nonce.pubkey()).await?);
+    signer.nonce_account = Some(nonce.pubkey());
+    Ok(())
+}
+
+/// Reads an answer to `question` from the terminal.
+fn prompt(question: &str) -> anyhow::Result<String> {
+    eprint!("{}", question);
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
+
+/// Asks for the owners and the threshold of a new multisig, validating each answer.
+fn ask_multisig_setup() -> anyhow::Result<(Vec<AnchorPubkey>, u64)> {
+    if !std::io::stdin().is_terminal() {
+        return Err(anyhow::Error::msg(
+            "--interactive needs a terminal, pass --signers and --threshold instead",
+        ));
+    }
+    let mut owners = Vec::new();
+    loop {
+        let answer = prompt(&format!(
+            "Owner #{} (empty line to finish): ",
+            owners.len() + 1
+        ))?;
+        if answer.is_empty() {
+            if owners.is_empty() {
+                warn("at least one owner is required");
+                continue;
+            }
+            break;
+        }
+        match answer.parse::<AnchorPubkey>() {
+            Ok(owner) if owners.contains(&owner) => warn(&format!("{} is already an owner", owner)),
+            Ok(owner) => owners.push(owner),
+            Err(e) => warn(&format!("invalid public key: {}", e)),
+        }
+    }
+    let threshold = loop {
+        let answer = prompt(&format!("Threshold (1-{}): ", owners.len()))?;
+        match answer.parse::<u64>() {
+            Ok(threshold) if (1..=owners.len() as u64).contains(&threshold) => break threshold,
+            _ => warn(&format!(
+                "the threshold must be a number between 1 and {}",
+                owners.len()
+            )),
+        }
+    };
+
+    eprintln!("{}", style("The multisig will be created with:").bold());
+    for (i, owner) in owners.iter().enumerate() {
+        eprintln!("Owner #{}: {}", i + 1, style(owner).green());
+    }
+    eprintln!(
+        "Threshold: {} of {}",
+        style(threshold).green(),
+        owners.len()
+    );
+    if prompt("Type \"yes\" to continue: ")? != "yes" {
+        return Err(anyhow::Error::msg("aborted"));
+    }
+    Ok((owners, threshold))
+}
+
+/// Genesis hash of mainnet, identifying it behind any RPC URL.
+const MAINNET_GENESIS_HASH: &str = "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d";
+
+/// Whether `cluster` is mainnet. Custom URLs are asked for their genesis
+/// hash, and offline taken for mainnet unless they point at this machine.
+async fn is_mainnet(cluster: &Cluster, offline: bool) -> anyhow::Result<bool> {
+    let url = match cluster {
+        Cluster::Mainnet => return Ok(true),
+        Cluster::Custom(url, _) => url,
+        _ => return Ok(false),
+    };
+    if offline {
+        return Ok(!self_test::is_local(cluster));
+    }
+    log::info!("fetching the genesis hash of {}", url);
+    let rpc = RpcClient::new(url.clone());
+    let genesis = rpc::retry(|| rpc.get_genesis_hash()).await?;
+    Ok(genesis.to_string() == MAINNET_GENESIS_HASH)
+}
+
+fn confirm_mainnet() -> anyhow::Result<()> {
+    eprintln!(
+        "{}",
+        style("WARNING: this command signs or submits a transaction on MAINNET and may move real funds.")
+            .red()
+            .bold()
+    );
+    if !std::io::stdin().is_terminal() {
+        return Err(anyhow::Error::msg(
+            "refusing to run on mainnet without --mainnet-confirm",
+        ));
+    }
+    if prompt("Type \"mainnet\" to continue: ")? != "mainnet" {
+        return Err(anyhow::Error::msg("aborted"));
+    }
+    Ok(())
+}
+
+/// Sends a transaction and waits for its confirmation.
+///
+/// A transaction using a recent blockhash rather than a durable nonce may
+/// expire before it lands. It is then signed again with a fresh blockhash,
+/// which is only possible when the local key is its only signer.
+async fn submit(
+    program: &Program<&Keypair>,
+    output: &Output,
+    mut tx: Transaction,
+    retries: u32,
+) -> anyhow::Result<Signature> {
+    let rpc = program.async_rpc();
+    let durable = durable_nonce_account(&tx.message).is_some();
+    let mut attempt = 0;
+    loop {
+        log::info!(
+            "sending transaction with {} signatures",
+            tx.signatures.len()
+        );
+        let err = match rpc::retry(|| rpc.send_and_confirm_transaction(&tx)).await {
+            Ok(sig) => return Ok(sig),
+            Err(err) => err,
+        };
+        if durable
+            || attempt >= retries
+            || rpc
+                .is_blockhash_valid(&tx.message.recent_blockhash, CommitmentConfig::processed())
+                .await?
+        {
+            return Err(err);
+        }
+
+        let signer = match sole_local_signer(output, &tx.message) {
+            Some(signer) => signer,
+            None => {
+                return Err(anyhow::Error::msg(
+                    "the blockhash expired and the transaction needs signatures of other parties, rebuild and sign it again",
+                ))
+            }
+        };
+        attempt += 1;
+        let blockhash = rpc.get_latest_blockhash().await?;
+        warn(&format!(
+            "the blockhash expired, signing again with {} ({}/{})",
+            blockhash, attempt, retries
+        ));
+        tx.try_sign(&[signer], blockhash)?;
+    }
+}
+
+/// Prints the PDA of a multisig about to be created and its associated token
+/// accounts for `mints`, for them to be funded or created in advance. Offline
+/// the token program of the mints is unknown and both candidates are printed.
+async fn print_creation_preview(
+    program: &Program<&Keypair>,
+    offline: bool,
+    multisig_pda: &AnchorPubkey,
+    mints: &[AnchorPubkey],
+) -> anyhow::Result<()> {
+    let token_programs: Vec<Vec<AnchorPubkey>> = if offline {
+        let both = vec![spl_token::id().to_bytes().into(), spl_token_2022::id()];
+        vec![both; mints.len()]
+    } else {
+        log::info!("fetching {} mint accounts", mints.len());
+        let accounts = get_multiple_accounts(program, mints).await?;
+        mints
+            .iter()
+            .zip(accounts)
+            .map(|(mint, account)| match account {
+                Some(account)
+                    if account.owner.to_bytes() == spl_token::id().to_bytes()
+                        || account.owner == spl_token_2022::id() =>
+                {
+                    Ok(vec![account.owner])
+                }
+                _ => Err(SerumError::Validation(format!("mint {} not found", mint)).into()),
+            })
+            .collect::<anyhow::Result<_>>()?
+    };
+    println!("Preview:");
+    println!("  Multisig PDA: {}", style(multisig_pda).green());
+    for (mint, programs) in mints.iter().zip(token_programs) {
+        for token_program in programs {
+            let ata: AnchorPubkey = get_associated_token_address_with_program_id(
+                &multisig_pda.to_bytes().into(),
+                &mint.to_bytes().into(),
+                &token_program.to_bytes().into(),
+            )
+            .to_bytes()
+            .into();
+            let program_name = if token_program == spl_token_2022::id() {
+                "Token-2022"
+            } else {
+                "Token"
             };
-            let req = program
-                .request()
-                .accounts(accounts)
-                .accounts(AccountMeta::new_readonly(sysvar::rent::id(), false))
-                .args(instructions)
-                .instruction(system_instruction::create_account(
-                    &signer.signer,
-                    &keypair.pubkey(),
-                    program.rpc().get_minimum_balance_for_rent_exemption(500)?,
-                    500,
-                    &program.id(),
-                ));
+            println!(
+                "  Token account of {} ({}): {}",
+                mint,
+                program_name,
+                style(ata).green()
+            );
+        }
+    }
+    Ok(())
+}
 
-            let tx = build_tx(
-                signer.signer,
-                signer.nonce,
-                signer.nonce_account,
-                req.instructions()?,
-            )?;
-            let sig = keypair.sign_message(&tx.serialize());
+/// Whether the recent blockhash of a transaction is still valid. The cluster
+/// rejects transactions with an expired one as "blockhash not found".
+async fn blockhash_is_valid(
+    program: &Program<&Keypair>,
+    blockhash: &hash::Hash,
+) -> anyhow::Result<bool> {
+    let rpc = program.async_rpc();
+    log::info!("checking that blockhash {} is still valid", blockhash);
+    rpc::retry(|| rpc.is_blockhash_valid(blockhash, CommitmentConfig::processed())).await
+}
+
+/// Local key when it is the only signer of `message`, which `submit` can
+/// sign again with a fresh blockhash.
+fn sole_local_signer<'a>(output: &'a Output, message: &Message) -> Option<&'a Keypair> {
+    let required = &message.account_keys[..message.header.num_required_signatures as usize];
+    output
+        .local_signer
+        .as_ref()
+        .filter(|signer| required == [signer.pubkey()].as_slice())
+}
+
+/// How long to wait for the confirmations requested with `--min-confirmations`.
+const CONFIRMATIONS_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Polls the status of `sig` until it is `min` confirmations deep or
+/// finalized, reporting each new depth.
+async fn wait_for_confirmations(
+    program: &Program<&Keypair>,
+    sig: &Signature,
+    min: usize,
+) -> anyhow::Result<()> {
+    let rpc = program.async_rpc();
+    let deadline = tokio::time::Instant::now() + CONFIRMATIONS_TIMEOUT;
+    let mut reported = None;
+    loop {
+        log::info!("fetching the status of {}", sig);
+        let status = rpc::retry(|| rpc.get_signature_statuses(&[*sig]))
+            .await?
+            .value
+            .into_iter()
+            .next()
+            .flatten();
+        if let Some(err) = status.as_ref().and_then(|status| status.err.as_ref()) {
+            return Err(anyhow::Error::msg(format!(
+                "transaction {} failed: {}",
+                sig, err
+            )));
+        }
+        // Finalized transactions have no confirmation count.
+        match status.map(|status| status.confirmations) {
+            Some(None) => {
+                println!("Confirmations: {}", style("finalized").green());
+                return Ok(());
+            }
+            Some(Some(count)) if count >= min => {
+                println!(
+                    "Confirmations: {}",
+                    style(format!("{} of {}", count, min)).green()
+                );
+                return Ok(());
+            }
+            Some(Some(count)) if reported != Some(count) => {
+                println!("Confirmations: {} of {}", count, min);
+                reported = Some(count);
+            }
+            _ => {}
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(anyhow::Error::msg(format!(
+                "transaction {} didn't reach {} confirmations within {} seconds, check its status later",
+                sig,
+                min,
+                CONFIRMATIONS_TIMEOUT.as_secs()
+            )));
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Like `submit`, printing the logs of transactions mentioning the fee payer
+/// as the websocket endpoint reports them. Submits without the logs if the
+/// endpoint is unavailable.
+async fn submit_streaming_logs(
+    program: &Program<&Keypair>,
+    output: &Output,
+    tx: Transaction,
+    retries: u32,
+    ws_url: &str,
+) -> anyhow::Result<Signature> {
+    let pubsub = match PubsubClient::new(ws_url).await {
+        Ok(pubsub) => pubsub,
+        Err(e) => {
+            warn(&format!("can't stream logs from {}: {}", ws_url, e));
+            return submit(program, output, tx, retries).await;
+        }
+    };
+    let fee_payer = tx.message.account_keys[0];
+    let filter = RpcTransactionLogsFilter::Mentions(vec![fee_payer.to_string()]);
+    let config = RpcTransactionLogsConfig {
+        commitment: Some(program.async_rpc().commitment()),
+    };
+    let (mut logs, unsubscribe) = match pubsub.logs_subscribe(filter, config).await {
+        Ok(subscription) => subscription,
+        Err(e) => {
+            warn(&format!("can't subscribe to the logs: {}", e));
+            return submit(program, output, tx, retries).await;
+        }
+    };
+
+    let submission = submit(program, output, tx, retries);
+    tokio::pin!(submission);
+    let result = loop {
+        tokio::select! {
+            result = &mut submission => break result,
+            Some(response) = logs.next() => {
+                let response = response.value;
+                println!("{}", style(format!("Logs of {}:", response.signature)).bold());
+                for line in response.logs {
+                    println!("  {}", line);
+                }
+                if let Some(err) = response.err {
+                    println!("  {}", style(format!("failed: {}", err)).red());
+                }
+            }
+        }
+    };
+    unsubscribe().await;
+    result
+}
 
+fn warn(message: &str) {
+    eprintln!("{} {}", style("Warning:").yellow().bold(), message);
+}
+
+static STRICT: AtomicBool = AtomicBool::new(false);
+
+/// Warns about an anomaly, or fails on it with `--strict`.
+fn caution(message: &str) -> anyhow::Result<()> {
+    if STRICT.load(Ordering::Relaxed) {
+        return Err(anyhow::Error::msg(format!("{} (--strict)", message)));
+    }
+    warn(message);
+    Ok(())
+}
+
+fn read_keypair(path: &str, format: KeypairFormat) -> anyhow::Result<Keypair> {
+    serum_common::keypair::read_keypair(path, format, Keypair::from_bytes)
+}
+
+/// Hands built transactions over to the signers in the requested format.
+struct Output {
+    format: OutputFormat,
+    encoding: Option<Encoding>,
+    local_signer: Option<Keypair>,
+    print_hash: bool,
+    session: bool,
+    qr: bool,
+}
+
+impl Output {
+    /// Prints the contents of the QR codes carrying `encoded`, one per line.
+    fn print_qr(&self, encoded: &str) {
+        if self.qr {
+            println!("Here are its QR chunks, to be scanned in order:\n");
+            for chunk in qr::chunks(encoded) {
+                println!("{}", chunk);
+            }
+            println!();
+        }
+    }
+
+    fn print(&self, message: &Message, signers: &[&dyn Signer]) -> anyhow::Result<()> {
+        if self.print_hash {
             println!(
-                "Transaction signed by transaction account: {}",
-                style(sig).green()
+                "Message hash (SHA-256): {}",
+                style(message_hash(message)).green()
             );
+        }
+        print_wire_size(message)?;
+        match self.format {
+            OutputFormat::Message => {
+                let encoding = self.encoding.unwrap_or(Encoding::Base64);
+                let encoded = encoding.encode(&message.serialize());
+                println!("You may now check the transaction using external tools.\nHere is the transaction data in {}:\n\n{}\n",
+                    encoding.name(),
+                    encoded
+                );
+                self.print_qr(&encoded);
+            }
+            OutputFormat::PartialTransaction => {
+                let required =
+                    &message.account_keys[..message.header.num_required_signatures as usize];
+                let signers: Vec<&dyn Signer> = signers
+                    .iter()
+                    .copied()
+                    .chain(self.local_signer.iter().map(|k| k as &dyn Signer))
+                    .filter(|s| required.contains(&s.pubkey()))
+                    .collect();
+                let mut tx = Transaction::new_unsigned(message.clone());
+                tx.try_partial_sign(&signers, message.recent_blockhash)?;
+                let encoding = self.encoding.unwrap_or(Encoding::Base58);
+                let encoded = encoding.encode(&bincode::serialize(&tx)?);
+                println!("You may now check the transaction using external tools.\nHere is the partially signed transaction in {}:\n\n{}\n",
+                    encoding.name(),
+                    encoded
+                );
+                self.print_qr(&encoded);
+            }
+            OutputFormat::Manifest => {
+                let request = manifest::SigningRequest::new(message)?;
+                let path = request.file_name();
+                request.write(path.as_ref())?;
+                println!(
+                    "Signing request written to {}, sign it with solana-signer --request\n",
+                    style(path).green()
+                );
+            }
+        }
+        if self.session {
+            let required = &message.account_keys[..message.header.num_required_signatures as usize];
+            let data = message.serialize();
+            let signatures: Vec<_> = signers
+                .iter()
+                .copied()
+                .chain(self.local_signer.iter().map(|k| k as &dyn Signer))
+                .filter(|s| required.contains(&s.pubkey()))
+                .map(|s| Ok((s.pubkey(), s.try_sign_message(&data)?)))
+                .collect::<anyhow::Result<_>>()?;
+            let session = signing_session::SigningSession::new(message, &signatures);
+            let path = session.file_name();
+            session.write(path.as_ref())?;
             println!(
-                "Pending transaction account: {}",
-                style(keypair.pubkey()).green()
+                "Signing session written to {}, collect the signatures with AddSignatures\n",
+                style(path).green()
             );
         }
-        Command::Approve {
+        Ok(())
+    }
+}
+
+/// Decodes a base64 message given directly or as `@FILE`.
+fn read_message_arg(arg: &str) -> anyhow::Result<Message> {
+    let encoded = match arg.strip_prefix('@') {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|e| anyhow::Error::msg(format!("failed to read {}: {}", path, e)))?,
+        None => arg.to_string(),
+    };
+    let message: Message = bincode::deserialize(&BASE64_STANDARD.decode(encoded.trim())?)?;
+    sanitize_message(&message)?;
+    Ok(message)
+}
+
+/// Rejects a message whose indexes point outside of its accounts, before
+/// anything indexes them.
+fn sanitize_message(message: &Message) -> anyhow::Result<()> {
+    message
+        .sanitize()
+        .map_err(|e| SerumError::Validation(format!("malformed message: {}", e)).into())
+}
+
+/// Headroom below the packet limit under which a transaction is reported as close to it.
+const WIRE_SIZE_WARNING_HEADROOM: usize = 100;
+
+/// Prints the size of the signed transaction relative to the packet limit.
+fn print_wire_size(message: &Message) -> anyhow::Result<()> {
+    let size = bincode::serialize(&Transaction::new_unsigned(message.clone()))?.len();
+    println!("Transaction size: {} of {} bytes", size, PACKET_DATA_SIZE);
+    if size > PACKET_DATA_SIZE {
+        caution(&format!(
+            "the transaction exceeds the packet limit by {} bytes and can't be submitted; it needs fewer accounts, or a versioned transaction with an address lookup table",
+            size - PACKET_DATA_SIZE
+        ))?;
+    } else if PACKET_DATA_SIZE - size < WIRE_SIZE_WARNING_HEADROOM {
+        caution(&format!(
+            "only {} bytes are left below the packet limit, larger instructions may need an address lookup table",
+            PACKET_DATA_SIZE - size
+        ))?;
+    }
+    Ok(())
+}
+
+/// SHA-256 of the serialized message, the bytes every signer signs.
+fn message_hash(message: &Message) -> hash::Hash {
+    hash::hash(&message.serialize())
+}
+
+fn build_tx(
+    payer: AnchorPubkey,
+    nonce: hash::Hash,
+    nonce_authority: AnchorPubkey,
+    instructions: Vec<Instruction>,
+) -> anyhow::Result<Message> {
+    for (i, ix) in instructions.iter().enumerate() {
+        log::debug!(
+            "instruction {}: program {}, {} accounts, {} bytes of data",
+            i,
+            ix.program_id,
+            ix.accounts.len(),
+            ix.data.len()
+        );
+    }
+    let mut message = Message::new_with_nonce(instructions, Some(&payer), &nonce_authority, &payer);
+    message.recent_blockhash = nonce;
+    log::debug!("serialized message is {} bytes", message.serialize().len());
+
+    Ok(message)
+}
+
+fn init_logger(verbose: u8) {
+    let mut builder = env_logger::Builder::from_default_env();
+    match verbose {
+        0 => {}
+        1 => {
+            builder.filter_module(module_path!(), log::LevelFilter::Info);
+        }
+        2 => {
+            builder.filter_module(module_path!(), log::LevelFilter::Debug);
+        }
+        // Also show the RPC client internals.
+        _ => {
+            builder.filter_level(log::LevelFilter::Debug);
+        }
+    }
+    builder.init();
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let result = match cli.timeout {
+        Some(seconds) => tokio::time::timeout(Duration::from_secs(seconds), run(cli))
+            .await
+            .unwrap_or_else(|_| {
+                Err(anyhow::Error::msg(format!(
+                    "the command didn't finish within the --timeout of {} seconds, transactions it submitted may still land",
+                    seconds
+                )))
+            }),
+        None => run(cli).await,
+    };
+    if let Err(err) = result {
+        eprintln!("Error: {:?}", err);
+        std::process::exit(error::exit_code(&err));
+    }
+}
+
+async fn run(mut cli: Cli) -> anyhow::Result<()> {
+    init_logger(cli.verbose);
+    rpc::set_retries(cli.rpc_retries);
+    STRICT.store(cli.strict, Ordering::Relaxed);
+    color::set_enabled(!cli.no_color && color::enabled_by_default());
+    let profile = match &cli.profile {
+        Some(name) => {
+            let path = cli
+                .config
+                .clone()
+                .or_else(config::Config::default_path)
+                .ok_or(anyhow::Error::msg(
+                    "config file not found, pass it with --config",
+                ))?;
+            config::Config::load(&path)?.take_profile(name)?
+        }
+        None => config::Profile::default(),
+    };
+    let registry = match cli.registry.clone() {
+        Some(path) => Some(config::Registry::load(&path)?),
+        None => match config::Registry::default_path() {
+            Some(path) if path.exists() => Some(config::Registry::load(&path)?),
+            _ => None,
+        },
+    };
+    let registered = match (&registry, cli.command.multisig()) {
+        (Some(registry), Some(multisig)) => registry.get(&multisig),
+        _ => None,
+    };
+    let registered_pid = registered.map(config::RegistryEntry::pid).transpose()?;
+    let pid = match cli.pid {
+        Some(pid) => pid,
+        None => match registered_pid {
+            Some(pid) => pid,
+            // Below the registry, whose entry is checked against the multisig.
+            None => match std::env::var("SERUM_PID") {
+                Ok(pid) => pid
+                    .parse()
+                    .map_err(|_| anyhow::Error::msg(format!("malformed SERUM_PID {:?}", pid)))?,
+                Err(_) => profile.pid()?.unwrap_or(DEFAULT_PID.parse()?),
+            },
+        },
+    };
+    let cluster = match cli.cluster {
+        Some(cluster) => cluster,
+        None => match registered
+            .map(config::RegistryEntry::cluster)
+            .transpose()?
+            .flatten()
+        {
+            Some(cluster) => cluster,
+            None => profile.cluster()?.unwrap_or(Cluster::Devnet),
+        },
+    };
+    let key_file = cli.key_file.or(profile.private_key());
+    let signs = cli.command.signs()
+        || (matches!(cli.output_format, OutputFormat::PartialTransaction) && key_file.is_some())
+        || cli
+            .command
+            .signer_mut()
+            .is_some_and(|signer| signer.auto_nonce);
+    let mainnet = is_mainnet(&cluster, cli.offline).await?;
+    if mainnet && signs && !cli.mainnet_confirm {
+        confirm_mainnet()?;
+    }
+
+    let dummy_signer = Keypair::new();
+    let client = Client::new_with_options(
+        cluster.clone(),
+        &dummy_signer,
+        cli.command.commitment().unwrap_or(cli.commitment).config(),
+    );
+
+    // Program instance
+    let program = client.program(pid)?;
+    if !cli.offline {
+        check_accounts_exist(&program, &cluster, &cli.command.existing_accounts()).await?;
+    }
+    if let (Some(multisig), Some(registered_pid)) = (cli.command.multisig(), registered_pid) {
+        if pid == registered_pid && !cli.offline {
+            // Fails unless the multisig signer bump matches the stored nonce.
+            load_multisig(&program, multisig).await.map_err(|e| {
+                e.context(format!(
+                    "the registry entry of multisig {} is wrong",
+                    multisig
+                ))
+            })?;
+        }
+    }
+    let mut mint_decimals = MintDecimals::new(&cli.decimals);
+    let output = Output {
+        format: cli.output_format,
+        encoding: cli.encoding,
+        local_signer: key_file
+            .as_deref()
+            .map(|path| read_keypair(path, cli.keypair_format))
+            .transpose()?,
+        print_hash: cli.print_message_hash,
+        session: cli.session,
+        qr: cli.qr,
+    };
+
+    let mut command = cli.command;
+    if !command.creates_account()
+        && command
+            .signer_mut()
+            .is_some_and(|signer| signer.rent_payer.is_some())
+    {
+        return Err(SerumError::Validation(
+            "--rent-payer only applies to commands creating an account".to_string(),
+        )
+        .into());
+    }
+    if let Some(memo) = cli.outer_memo {
+        if !command.proposes_or_executes() {
+            return Err(SerumError::Validation(
+                "--outer-memo only applies to commands creating or executing a proposal"
+                    .to_string(),
+            )
+            .into());
+        }
+        if let Some(signer) = command.signer_mut() {
+            signer.outer_memo = Some(memo);
+        }
+    }
+    if let Some(signer) = command.signer_mut().filter(|signer| signer.auto_nonce) {
+        if cli.offline {
+            return Err(anyhow::Error::msg(
+                "--auto-nonce creates the nonce account on the cluster and can't be used offline",
+            ));
+        }
+        create_auto_nonce(&program, &output, signer).await?;
+    }
+    if let Some(signer) = command.signer_mut().filter(|_| !cli.offline) {
+        check_nonce_authority(&program, signer).await?;
+    }
+    match command {
+        Command::CreateMultisig {
             signer,
+            signers,
+            threshold,
+            interactive,
+            preview_mints,
+        } => {
+            let preview_mints = preview_mints
+                .into_iter()
+                .map(|mint| mint.resolve(mainnet))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let (signers, threshold) = match threshold {
+                Some(threshold) if !interactive => (signers, threshold),
+                _ => ask_multisig_setup()?,
+            };
+            let keypair = Keypair::new();
+            let (multisig_pda, _) = derive_multisig_signer(&keypair.pubkey(), &pid);
+            let tx = create_multisig_message(
+                &program,
+                cli.offline,
+                &signer,
+                signers,
+                threshold,
+                keypair.pubkey(),
+            )
+            .await?;
+            let rent = rent_exemption(&program, cli.offline, MULTISIG_ACCOUNT_SIZE).await?;
+            check_funds(
+                &program,
+                cli.offline,
+                &tx,
+                &signer,
+                &[(signer.rent_payer(), rent)],
+            )
+            .await?;
+            if !preview_mints.is_empty() {
+                print_creation_preview(&program, cli.offline, &multisig_pda, &preview_mints)
+                    .await?;
+            }
+            output.print(&tx, &[&keypair])?;
+            let sig = keypair.sign_message(&tx.serialize());
+
+            if signer.rent_payer() != signer.signer {
+                println!(
+                    "Rent payer, signing along with the signer: {}",
+                    signer.rent_payer()
+                );
+            }
+            println!("Transaction signed by multisig account: {}", sig);
+            println!("Multisig address: {}", keypair.pubkey());
+            println!("Multisig PDA: {}", multisig_pda);
+        }
+        Command::CreateTokenTransferTransaction {
+            mut signer,
             multisig,
-            transaction,
+            from,
+            to,
+            amount,
+            raw_amount,
+            mint,
+            with_transfer_fee,
+            count,
+            allow_create_destination,
+            ata_payer,
         } => {
+            let mint = mint.map(|mint| mint.resolve(mainnet)).transpose()?;
+            if count > 1 {
+                if mainnet {
+                    return Err(SerumError::Validation(
+                        "--count is only available outside mainnet".to_string(),
+                    )
+                    .into());
+                }
+                if !signer.auto_nonce {
+                    return Err(SerumError::Validation(
+                        "proposals can't share a nonce, pass --auto-nonce with --count".to_string(),
+                    )
+                    .into());
+                }
+            }
+            let amount = match (amount, raw_amount) {
+                (_, Some(raw)) => Amount::Raw(raw),
+                (Some(amount), None) => amount,
+                (None, None) => unreachable!("clap requires one of the amounts"),
+            };
+            if let Some((mint, known)) = mint
+                .filter(|_| mainnet)
+                .and_then(|mint| Some((mint, tokens::decimals(&mint)?)))
+            {
+                // Checked against the decimals of the source account's mint below.
+                mint_decimals.observe(mint, known)?;
+            }
+            if !cli.offline {
+                amount.check_online()?;
+                load_multisig(&program, multisig).await?;
+            } else if let (Some(mint), Some(given)) = (mint, amount.decimals()) {
+                mint_decimals.observe(mint, given)?;
+            }
+
+            let (to, setup, source) = if allow_create_destination {
+                if cli.offline {
+                    return Err(anyhow::Error::msg(
+                        "creating the destination reads whether it exists and can't be done offline",
+                    ));
+                }
+                let payer = ata_payer.unwrap_or(signer.signer);
+                let source =
+                    fetch_token_account(&program, &from, "source", &mut mint_decimals).await?;
+                let (ata, setup) = destination_ata(&program, &source, &to, &payer).await?;
+                if !setup.instructions.is_empty() && payer != signer.signer {
+                    println!(
+                        "ATA payer, signing along with the signer: {}",
+                        style(payer).green()
+                    );
+                }
+                (ata, setup, Some(source))
+            } else {
+                (to, Setup::default(), None)
+            };
+
+            let (amount, decimals, mint, token_program) = if cli.offline {
+                if with_transfer_fee {
+                    return Err(anyhow::Error::msg(
+                        "transfer fees are read from the mint and can't be computed offline",
+                    ));
+                }
+                let mint = mint.ok_or(anyhow::Error::msg("mint is required in offline mode"))?;
+                let decimals = mint_decimals.get(&mint).ok_or(anyhow::Error::msg(
+                    "mint decimals are required in offline mode, pass them with --decimals or as AMOUNT@DECIMALS",
+                ))?;
+                // Offline, the source is taken for a Token account.
+                (
+                    amount.to_raw(decimals, None)?,
+                    decimals,
+                    mint,
+                    spl_token::id().to_bytes().into(),
+                )
+            } else {
+                let from_account = match source {
+                    // The destination is created from the source's mint.
+                    Some(source) if !setup.instructions.is_empty() => source,
+                    _ => {
+                        fetch_transfer_accounts(&program, &from, &to, &mut mint_decimals)
+                            .await?
+                            .0
+                    }
+                };
+                if mint.is_some_and(|mint| mint != from_account.mint) {
+                    return Err(SerumError::Validation(
+                        "source account doesn't belong to the given mint".to_string(),
+                    )
+                    .into());
+                }
+                let decimals = mint_decimals.observe(from_account.mint, from_account.decimals)?;
+                let resolved = amount.to_raw(decimals, Some(from_account.amount))?;
+                if let Amount::Share {
+                    numerator,
+                    denominator,
+                } = amount
+                {
+                    println!(
+                        "{}% of the balance of {} is {}",
+                        numerator as f64 * 100.0 / denominator as f64,
+                        decode::format_amount(from_account.amount, decimals),
+                        style(decode::format_amount(resolved, decimals)).green()
+                    );
+                    if resolved == 0 {
+                        return Err(SerumError::Validation(
+                            "the percentage of the balance rounds down to nothing".to_string(),
+                        )
+                        .into());
+                    }
+                }
+                let amount = resolved;
+                if from_account.amount < amount {
+                    return Err(SerumError::InsufficientFunds(
+                        "source account doesn't have sufficient amount of token".to_string(),
+                    )
+                    .into());
+                }
+                (amount, decimals, from_account.mint, from_account.program_id)
+            };
+            log::debug!("transfer amount in base units: {}", amount);
+            let fee = if with_transfer_fee {
+                Some(transfer_fee(&program, &mint, amount).await?)
+            } else {
+                None
+            };
+
             println!(
                 "{}",
-                "Approving a transaction with the following parameters:".bold()
+                style("Preparing a token transfer transaction with the following parameters:")
+                    .bold()
             );
             println!(
-                "Multisig address: {}\nTransaction address: {}\n",
+                "Multisig address: {}\nFrom address: {}\nTo address: {}\nAmount: {}\n",
                 style(multisig).green(),
-                style(transaction).green(),
+                style(from).green(),
+                style(to).green(),
+                style(decode::format_amount(amount, decimals)).green(),
             );
+            if let Some(fee) = fee {
+                println!(
+                    "Transfer fee withheld from the amount: {}\n",
+                    style(decode::format_amount(fee, decimals)).green()
+                );
+            }
 
-            let accounts = multisig_accounts::Approve {
-                multisig,
-                transaction,
-                owner: signer.signer,
+            let (multisig_pda, _) = derive_multisig_signer(&multisig, &pid);
+            let transfer = match fee {
+                Some(fee) => {
+                    to_spl_instruction(transfer_fee_instruction::transfer_checked_with_fee(
+                        &spl_token_2022::id(),
+                        &from,
+                        &mint,
+                        &to,
+                        &multisig_pda,
+                        &[],
+                        amount,
+                        decimals,
+                        fee,
+                    )?)
+                }
+                None => token_transfer(
+                    &token_program,
+                    &mint,
+                    decimals,
+                    &from,
+                    &to,
+                    &multisig_pda,
+                    amount,
+                )?,
             };
-            let instructions = multisig_instructions::Approve {};
-            let req = program.request().accounts(accounts).args(instructions);
-
-            build_tx(
-                signer.signer,
-                signer.nonce,
-                signer.nonce_account,
-                req.instructions()?,
-            )?;
+            if count == 1 {
+                propose_transaction(
+                    &program,
+                    cli.offline,
+                    &output,
+                    &signer,
+                    multisig,
+                    transfer,
+                    setup,
+                )
+                .await?;
+            } else {
+                // A destination created by the proposals can't be checked yet.
+                if !cli.offline && setup.instructions.is_empty() {
+                    let transfers: Vec<_> = (0..count)
+                        .map(|_| PlannedTransfer { from, to, amount })
+                        .collect();
+                    check_transfers(&program, &transfers, &mut mint_decimals).await?;
+                }
+                let mut proposed = Vec::new();
+                for i in 0..count {
+                    if i > 0 {
+                        create_auto_nonce(&program, &output, &mut signer).await?;
+                    }
+                    println!(
+                        "{}",
+                        style(format!("Proposal {} of {}", i + 1, count)).bold()
+                    );
+                    proposed.push(
+                        propose_transaction(
+                            &program,
+                            cli.offline,
+                            &output,
+                            &signer,
+                            multisig,
+                            transfer.clone(),
+                            setup.clone(),
+                        )
+                        .await?,
+                    );
+                }
+                println!("{}", style("Pending transaction accounts:").bold());
+                for transaction in proposed {
+                    println!("{}", transaction);
+                }
+            }
         }
-        Command::ExecuteTokenTransferTransaction {
-            signer,
+        Command::CreatePayoutTransactions {
+            mut signer,
             multisig,
-            transaction,
+            from,
+            csv,
         } => {
-            let transaction_account: coral_multisig::Transaction =
-                program.account(transaction).await?;
-            let (multisig_pda, _) = derive_multisig_signer(&multisig, &cli.pid);
-            let mut remaining_accounts: Vec<AccountMeta> = transaction_account
-                .accounts
+            if cli.offline {
+                return Err(anyhow::Error::msg(
+                    "payouts are checked against the source balance and can't be created offline",
+                ));
+            }
+            let payouts = read_payouts(&csv)?;
+            if payouts.len() > 1 && !signer.auto_nonce {
+                return Err(SerumError::Validation(
+                    "proposals can't share a nonce, pass --auto-nonce to create several payouts"
+                        .to_string(),
+                )
+                .into());
+            }
+            load_multisig(&program, multisig).await?;
+            let source = fetch_token_account(&program, &from, "source", &mut mint_decimals).await?;
+            let decimals = mint_decimals.observe(source.mint, source.decimals)?;
+            let transfers: Vec<_> = payouts
                 .iter()
-                .map(Into::into)
+                .map(|payout| PlannedTransfer {
+                    from,
+                    to: payout.recipient,
+                    amount: spl_token::ui_amount_to_amount(payout.amount, decimals),
+                })
                 .collect();
-            for acc in remaining_accounts.iter_mut() {
-                acc.is_signer = false;
+            if let Some(transfer) = transfers.iter().find(|transfer| transfer.amount == 0) {
+                return Err(SerumError::Validation(format!(
+                    "the payout to {} rounds down to nothing",
+                    transfer.to
+                ))
+                .into());
+            }
+            check_transfers(&program, &transfers, &mut mint_decimals).await?;
+            let total: u64 = transfers.iter().map(|transfer| transfer.amount).sum();
+
+            println!(
+                "{}",
+                style("Preparing payout transactions with the following parameters:").bold()
+            );
+            println!(
+                "Multisig address: {}\nFrom address: {}\nPayouts: {}\nTotal amount: {}\n",
+                style(multisig).green(),
+                style(from).green(),
+                style(transfers.len()).green(),
+                style(decode::format_amount(total, decimals)).green(),
+            );
+
+            let (multisig_pda, _) = derive_multisig_signer(&multisig, &pid);
+            let outer_memo = signer.outer_memo.clone();
+            let mut proposed = Vec::new();
+            for (i, (payout, transfer)) in payouts.iter().zip(&transfers).enumerate() {
+                if i > 0 {
+                    create_auto_nonce(&program, &output, &mut signer).await?;
+                }
+                println!(
+                    "{}",
+                    style(format!(
+                        "Payout {} of {}: {} to {}",
+                        i + 1,
+                        transfers.len(),
+                        decode::format_amount(transfer.amount, decimals),
+                        transfer.to
+                    ))
+                    .bold()
+                );
+                signer.outer_memo = payout.memo.clone().or_else(|| outer_memo.clone());
+                let instruction = token_transfer(
+                    &source.program_id,
+                    &source.mint,
+                    decimals,
+                    &from,
+                    &transfer.to,
+                    &multisig_pda,
+                    transfer.amount,
+                )?;
+                proposed.push(
+                    propose_transaction(
+                        &program,
+                        cli.offline,
+                        &output,
+                        &signer,
+                        multisig,
+                        instruction,
+                        Setup::default(),
+                    )
+                    .await?,
+                );
+            }
+            println!("{}", style("Pending transaction accounts:").bold());
+            for (transfer, transaction) in transfers.iter().zip(proposed) {
+                println!(
+                    "{} {} to {}",
+                    transaction,
+                    decode::format_amount(transfer.amount, decimals),
+                    transfer.to
+                );
+            }
+            println!(
+                "Total disbursed: {}",
+                style(decode::format_amount(total, decimals)).green()
+            );
+        }
+        Command::CreateDrainTransaction {
+            signer,
+            multisig,
+            from,
+            to,
+        } => {
+            if cli.offline {
+                return Err(anyhow::Error::msg(
+                    "draining reads the source balance and can't be done offline",
+                ));
+            }
+            load_multisig(&program, multisig).await?;
+            let (from_account, _) =
+                fetch_transfer_accounts(&program, &from, &to, &mut mint_decimals).await?;
+            if from_account.amount == 0 {
+                return Err(
+                    SerumError::InsufficientFunds("source account is empty".to_string()).into(),
+                );
+            }
+            let decimals = mint_decimals.observe(from_account.mint, from_account.decimals)?;
+
+            println!(
+                "{}",
+                style("Preparing a token drain transaction with the following parameters:").bold()
+            );
+            println!(
+                "Multisig address: {}\nFrom address: {}\nTo address: {}\nAmount: {}\n",
+                style(multisig).green(),
+                style(from).green(),
+                style(to).green(),
+                style(decode::format_amount(from_account.amount, decimals)).green(),
+            );
+
+            let (multisig_pda, _) = derive_multisig_signer(&multisig, &pid);
+            let transfer = token_transfer(
+                &from_account.program_id,
+                &from_account.mint,
+                decimals,
+                &from,
+                &to,
+                &multisig_pda,
+                from_account.amount,
+            )?;
+            propose_transaction(
+                &program,
+                cli.offline,
+                &output,
+                &signer,
+                multisig,
+                transfer,
+                Setup::default(),
+            )
+            .await?;
+        }
+        Command::CreateApproveDelegateTransaction {
+            signer,
+            multisig,
+            account,
+            delegate,
+            amount,
+        } => {
+            if cli.offline {
+                return Err(anyhow::Error::msg(
+                    "delegating reads the token account and can't be done offline",
+                ));
+            }
+            amount.check_online()?;
+            load_multisig(&program, multisig).await?;
+            let token_account =
+                fetch_token_account(&program, &account, "delegating", &mut mint_decimals).await?;
+            let decimals = mint_decimals.observe(token_account.mint, token_account.decimals)?;
+            let amount = amount.to_raw(decimals, Some(token_account.amount))?;
+
+            println!(
+                "{}",
+                style("Preparing a token delegation transaction with the following parameters:")
+                    .bold()
+            );
+            println!(
+                "Multisig address: {}\nAccount: {}\nDelegate: {}\nAmount: {}\n",
+                style(multisig).green(),
+                style(account).green(),
+                style(delegate).green(),
+                style(decode::format_amount(amount, decimals)).green(),
+            );
+
+            let (multisig_pda, _) = derive_multisig_signer(&multisig, &pid);
+            let approve = to_spl_instruction(spl_token_2022::instruction::approve(
+                &token_account.program_id,
+                &account,
+                &delegate,
+                &multisig_pda,
+                &[],
+                amount,
+            )?);
+            propose_transaction(
+                &program,
+                cli.offline,
+                &output,
+                &signer,
+                multisig,
+                approve,
+                Setup::default(),
+            )
+            .await?;
+        }
+        Command::CreateRevokeTransaction {
+            signer,
+            multisig,
+            account,
+        } => {
+            let token_program = if cli.offline {
+                spl_token::id().to_bytes().into()
+            } else {
+                load_multisig(&program, multisig).await?;
+                fetch_token_account(&program, &account, "delegating", &mut mint_decimals)
+                    .await?
+                    .program_id
+            };
+            println!(
+                "{}",
+                style(
+                    "Preparing a delegation revocation transaction with the following parameters:"
+                )
+                .bold()
+            );
+            println!(
+                "Multisig address: {}\nAccount: {}\n",
+                style(multisig).green(),
+                style(account).green(),
+            );
+
+            let (multisig_pda, _) = derive_multisig_signer(&multisig, &pid);
+            let revoke = to_spl_instruction(spl_token_2022::instruction::revoke(
+                &token_program,
+                &account,
+                &multisig_pda,
+                &[],
+            )?);
+            propose_transaction(
+                &program,
+                cli.offline,
+                &output,
+                &signer,
+                multisig,
+                revoke,
+                Setup::default(),
+            )
+            .await?;
+        }
+        Command::CreateRawTransaction {
+            signer,
+            multisig,
+            program_id,
+            mut accounts,
+            data,
+            pda_signer_index,
+            max_accounts,
+        } => {
+            let data = BASE64_STANDARD.decode(data)?;
+            // Each owner takes a byte for its approval; offline the owners are
+            // unknown and the capacity is an upper bound.
+            let owners = if cli.offline {
+                0
+            } else {
+                load_multisig(&program, multisig).await?.owners.len()
+            };
+            let capacity = transaction_account_capacity(data.len(), owners);
+            println!(
+                "Transaction account capacity: {} accounts with {} bytes of data",
+                capacity,
+                data.len()
+            );
+            let max_accounts = max_accounts.unwrap_or(capacity);
+            if accounts.len() > max_accounts {
+                return Err(SerumError::Validation(format!(
+                    "the instruction has {} accounts, more than the {} allowed; the {}-byte transaction account fits {}",
+                    accounts.len(),
+                    max_accounts,
+                    TRANSACTION_ACCOUNT_SIZE,
+                    capacity
+                ))
+                .into());
+            }
+            let (multisig_pda, _) = derive_multisig_signer(&multisig, &pid);
+            let pda: spl_token::solana_program::pubkey::Pubkey = multisig_pda.to_bytes().into();
+            // The program signs for the multisig signer only, every other
+            // signer of the instruction would have to sign the execution.
+            match pda_signer_index {
+                Some(index) => {
+                    let account = accounts.get_mut(index).ok_or_else(|| {
+                        anyhow::Error::msg(format!(
+                            "--pda-signer-index {} is out of the {} accounts",
+                            index,
+                            accounts.len()
+                        ))
+                    })?;
+                    if account.pubkey != pda {
+                        return Err(anyhow::Error::msg(format!(
+                            "account #{} is {}, not the multisig signer {}",
+                            index, account.pubkey, multisig_pda
+                        )));
+                    }
+                    account.is_signer = true;
+                }
+                None => {
+                    for account in accounts.iter_mut().filter(|acc| acc.pubkey == pda) {
+                        account.is_signer = true;
+                    }
+                }
+            }
+            if !accounts.iter().any(|acc| acc.is_signer) {
+                caution(&format!(
+                    "the multisig signer {} is not among the accounts, the instruction won't be authorized by the multisig",
+                    multisig_pda
+                ))?;
+            }
+
+            println!(
+                "{}",
+                style("Preparing a transaction with the following instruction:").bold()
+            );
+            println!(
+                "Multisig address: {}\nProgram: {}",
+                style(multisig).green(),
+                style(program_id).green(),
+            );
+            for acc in &accounts {
+                let mut flags = Vec::new();
+                if acc.is_signer {
+                    flags.push("signer");
+                }
+                if acc.is_writable {
+                    flags.push("writable");
+                }
+                println!("Account: {} {:?}", acc.pubkey, flags);
             }
-            let from_account = program
-                .async_rpc()
-                .get_token_account(&remaining_accounts[0].pubkey)
+            println!();
+
+            let instruction = SplInstruction {
+                program_id: program_id.to_bytes().into(),
+                accounts,
+                data,
+            };
+            propose_transaction(
+                &program,
+                cli.offline,
+                &output,
+                &signer,
+                multisig,
+                instruction,
+                Setup::default(),
+            )
+            .await?;
+        }
+        Command::CreateSolTransferTransaction {
+            signer,
+            multisig,
+            to,
+            amount,
+            all,
+            keep_rent_exempt,
+        } => {
+            let (multisig_pda, _) = derive_multisig_signer(&multisig, &pid);
+            let mut lamports = amount.map(sol_to_lamports);
+            if cli.offline {
+                if all || keep_rent_exempt {
+                    return Err(anyhow::Error::msg(
+                        "--all and --keep-rent-exempt read the balance and can't be used offline",
+                    ));
+                }
+                caution("the balance of the multisig signer can't be checked offline")?;
+            } else {
+                load_multisig(&program, multisig).await?;
+                log::info!("fetching multisig signer account {}", multisig_pda);
+                let rpc = program.async_rpc();
+                let (balance, data_len) = match rpc::retry(|| {
+                    rpc.get_account_with_commitment(&multisig_pda, rpc.commitment())
+                })
                 .await?
-                .ok_or(anyhow::Error::msg("source token account not found"))?;
-            let amount = match TokenInstruction::unpack(&transaction_account.data)? {
-                TokenInstruction::Transfer { amount } => Ok(amount),
-                _ => Err(anyhow::Error::msg(
-                    "transaction instruction is not transfer",
-                )),
-            }?;
-            let amount = spl_token::amount_to_ui_amount(amount, from_account.token_amount.decimals);
-            println!("Executing a token transfer transaction with the following parameters:");
+                .value
+                {
+                    Some(account) => (account.lamports, account.data.len()),
+                    None => (0, 0),
+                };
+                let minimum = rent_exemption(&program, false, data_len).await?;
+                let mut requested = lamports.unwrap_or(balance);
+                if keep_rent_exempt && balance.saturating_sub(requested) < minimum {
+                    requested = balance.saturating_sub(minimum);
+                    warn(&format!(
+                        "reduced the amount to {} to keep the multisig signer rent exempt",
+                        decode::format_lamports(requested)
+                    ));
+                }
+                if requested == 0 {
+                    return Err(anyhow::Error::msg("nothing to transfer"));
+                }
+                if requested > balance {
+                    return Err(SerumError::InsufficientFunds(format!(
+                        "the multisig signer only holds {}",
+                        decode::format_lamports(balance)
+                    ))
+                    .into());
+                }
+                let remaining = balance - requested;
+                if remaining == 0 {
+                    caution("the transfer fully drains the multisig signer")?;
+                } else if remaining < minimum {
+                    caution(&format!(
+                        "the transfer leaves {} in the multisig signer, below the rent exempt minimum of {}, and will fail; pass --keep-rent-exempt to reduce it",
+                        decode::format_lamports(remaining),
+                        decode::format_lamports(minimum)
+                    ))?;
+                }
+                lamports = Some(requested);
+            }
+            let lamports = lamports.expect("the amount is given or read from the balance");
+
+            println!(
+                "{}",
+                style("Preparing a SOL transfer transaction with the following parameters:").bold()
+            );
+            println!(
+                "Multisig address: {}\nFrom address: {}\nTo address: {}\nAmount: {}\n",
+                style(multisig).green(),
+                style(multisig_pda).green(),
+                style(to).green(),
+                style(decode::format_lamports(lamports)).green(),
+            );
+
+            let transfer =
+                to_spl_instruction(system_instruction::transfer(&multisig_pda, &to, lamports));
+            propose_transaction(
+                &program,
+                cli.offline,
+                &output,
+                &signer,
+                multisig,
+                transfer,
+                Setup::default(),
+            )
+            .await?;
+        }
+        Command::Approve {
+            signer,
+            multisig,
+            transaction,
+        } => {
             println!(
-                "Multisig address: {}\nTransaction address: {}\nFrom: {}\nTo: {}\nAmount: {}\n",
+                "{}",
+                style("Approving a transaction with the following parameters:").bold()
+            );
+            println!(
+                "Multisig address: {}\nTransaction address: {}\n",
                 style(multisig).green(),
                 style(transaction).green(),
-                style(remaining_accounts[0].pubkey).green(),
-                style(remaining_accounts[1].pubkey).green(),
-                style(amount).green(),
             );
 
-            let accounts = multisig_accounts::ExecuteTransaction {
+            if !cli.offline {
+                load_multisig(&program, multisig).await?;
+            }
+
+            let tx = approve_message(&program, &signer, multisig, transaction)?;
+            output.print(&tx, &[])?;
+        }
+        Command::ExecuteTokenTransferTransaction {
+            signer,
+            multisig,
+            transaction,
+            simulate,
+            force,
+            allowed_programs,
+            compute_budget,
+        } => {
+            let multisig_account = load_multisig(&program, multisig).await?;
+            let transaction_account = fetch_checked_transaction(&program, transaction).await?;
+            check_allowed_program(&transaction_account, &allowed_programs)?;
+            let approvals = ApprovalCount::new(&multisig_account, &transaction_account);
+            if transaction_account.did_execute {
+                let message = format!(
+                    "this transaction has already been executed, with {} of {} approvals; executing it again fails on-chain and wastes the nonce",
+                    approvals.approved, approvals.threshold
+                );
+                if !force {
+                    return Err(SerumError::Validation(format!(
+                        "{}, pass --force to build the execution anyway",
+                        message
+                    ))
+                    .into());
+                }
+                caution(&message)?;
+            }
+            if !approvals.is_met() {
+                caution(&format!(
+                    "the transaction has {} of {} required approvals, execution will fail until more owners approve",
+                    approvals.approved, approvals.threshold
+                ))?;
+            }
+            if let Some(delegation) = stored_delegation(&transaction_account) {
+                if simulate {
+                    return Err(anyhow::Error::msg(
+                        "--simulate shows token balances, which a delegation doesn't change",
+                    ));
+                }
+                println!("Executing a token delegation transaction with the following parameters:");
+                println!(
+                    "Multisig address: {}\nTransaction address: {}\nDelegation: {}\n",
+                    style(multisig).green(),
+                    style(transaction).green(),
+                    style(decode::describe_instruction(
+                        &transaction_account.program_id,
+                        &pid,
+                        &transaction_account.data
+                    ))
+                    .green(),
+                );
+                let (multisig_pda, _) = derive_multisig_signer(&multisig, &program.id());
+                if delegation.owner != multisig_pda {
+                    return Err(anyhow::Error::msg(format!(
+                        "the delegating account's owner is {}, not the multisig signer {}",
+                        delegation.owner, multisig_pda
+                    )));
+                }
+                let tx = execute_message(
+                    &program,
+                    &signer,
+                    multisig,
+                    transaction,
+                    &transaction_account,
+                    &compute_budget,
+                )?;
+                output.print(&tx, &[])?;
+            } else {
+                let StoredTransfer {
+                    from,
+                    to,
+                    amount,
+                    fee,
+                } = stored_transfer(&transaction_account)?;
+                let from_account =
+                    fetch_token_account(&program, &from, "source", &mut mint_decimals).await?;
+                log::debug!("stored transfer amount in base units: {}", amount);
+                let decimals = mint_decimals.observe(from_account.mint, from_account.decimals)?;
+                println!("Executing a token transfer transaction with the following parameters:");
+                println!(
+                    "Multisig address: {}\nTransaction address: {}\nFrom: {}\nTo: {}\nAmount: {}\n",
+                    style(multisig).green(),
+                    style(transaction).green(),
+                    style(from).green(),
+                    style(to).green(),
+                    style(decode::format_amount(amount, decimals)).green(),
+                );
+                if let Some(fee) = fee {
+                    println!(
+                        "Transfer fee withheld from the amount: {}\n",
+                        style(decode::format_amount(fee, decimals)).green()
+                    );
+                }
+
+                let (multisig_pda, _) = derive_multisig_signer(&multisig, &program.id());
+                check_transfer_accounts(&transaction_account, &multisig_pda)?;
+
+                let tx = execute_message(
+                    &program,
+                    &signer,
+                    multisig,
+                    transaction,
+                    &transaction_account,
+                    &compute_budget,
+                )?;
+                output.print(&tx, &[])?;
+
+                if simulate {
+                    let to_account =
+                        fetch_token_account(&program, &to, "destination", &mut mint_decimals)
+                            .await?;
+                    if let Some(balances) =
+                        simulate_token_balances(&program, &tx, &[from, to]).await?
+                    {
+                        println!("{}", style("Simulated balance changes:").bold());
+                        for ((role, address, before), after) in [
+                            ("Source", from, from_account.amount),
+                            ("Destination", to, to_account.amount),
+                        ]
+                        .into_iter()
+                        .zip(balances)
+                        {
+                            println!(
+                                "{} {}: {} -> {}",
+                                role,
+                                address,
+                                decode::format_amount(before, decimals),
+                                style(after.map_or("unknown".to_string(), |after| {
+                                    decode::format_amount(after, decimals)
+                                }))
+                                .green(),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        Command::ApproveAndExecute {
+            signer,
+            multisig,
+            transaction,
+            approvers,
+            as_bundle,
+            tip,
+            allowed_programs,
+            compute_budget,
+        } => {
+            let multisig_account = load_multisig(&program, multisig).await?;
+            log::info!("fetching transaction account {}", transaction);
+            let transaction_account: coral_multisig::Transaction =
+                rpc::retry(|| program.account(transaction)).await?;
+            check_allowed_program(&transaction_account, &allowed_programs)?;
+            if transaction_account.did_execute {
+                return Err(anyhow::Error::msg(
+                    "the transaction has already been executed",
+                ));
+            }
+            check_owner_set(&multisig_account, &transaction_account)?;
+            let approvals = ApprovalCount::new(&multisig_account, &transaction_account);
+            let missing = approvals.threshold.saturating_sub(approvals.approved) as usize;
+
+            let mut seen = HashSet::new();
+            let pending: Vec<&SignerArg> = approvers
+                .iter()
+                .filter(|approver| seen.insert(approver.signer))
+                .filter(|approver| {
+                    multisig_account
+                        .owners
+                        .iter()
+                        .position(|owner| *owner == approver.signer)
+                        .is_some_and(|i| {
+                            !transaction_account.signers.get(i).copied().unwrap_or(false)
+                        })
+                })
+                .take(missing)
+                .collect();
+            if pending.len() < missing {
+                return Err(SerumError::ThresholdNotMet(format!(
+                    "{} more approvals are needed, but only {} of the given approvers are owners who haven't approved yet",
+                    missing,
+                    pending.len()
+                ))
+                .into());
+            }
+
+            let mut steps = Vec::new();
+            for approver in pending {
+                steps.push((
+                    format!("approval by {}", approver.signer),
+                    approve_message(&program, approver, multisig, transaction)?,
+                ));
+            }
+            let mut instructions = execute_instructions(
+                &program,
+                &signer,
                 multisig,
-                multisig_signer: multisig_pda,
                 transaction,
-            };
-            let instructions = multisig_instructions::ExecuteTransaction {};
-            let req = program
-                .request()
-                .accounts(accounts)
-                .accounts(remaining_accounts)
-                .accounts(AccountMeta::new(spl_token::id().to_bytes().into(), false))
-                .args(instructions);
-
-            build_tx(
-                signer.signer,
-                signer.nonce,
-                signer.nonce_account,
-                req.instructions()?,
+                &transaction_account,
+                &compute_budget,
             )?;
+            instructions.extend(tip.map(|tip| bundle::tip_instruction(&signer.signer, tip)));
+            steps.push((
+                format!("execution by {}", signer.signer),
+                build_tx(
+                    signer.signer,
+                    signer.nonce()?,
+                    signer.nonce_account()?,
+                    instructions,
+                )?,
+            ));
+
+            if as_bundle {
+                let bundle: Vec<SignedTransaction> = steps
+                    .iter()
+                    .map(|(_, tx)| SignedTransaction::unsigned(tx))
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&bundle)?);
+            } else {
+                for (step, (description, tx)) in steps.iter().enumerate() {
+                    println!(
+                        "{}",
+                        style(format!("Step {}: {}", step + 1, description)).bold()
+                    );
+                    output.print(tx, &[])?;
+                }
+            }
         }
         Command::Submit {
             transaction,
             signatures,
+            from_session,
+            from_qr,
+            signature_dir,
+            force,
+            require_confirmed_nonce,
+            stream_logs,
+            skip_verify,
+            min_confirmations,
+            ..
         } => {
-            let data = BASE64_STANDARD.decode(transaction)?;
-            let message: Message = bincode::deserialize(&data)?;
+            let transaction = from_qr
+                .map(|path| qr::read(&path))
+                .transpose()?
+                .or(transaction);
+            let (message, signatures) = match (from_session, transaction) {
+                (Some(path), _) => {
+                    let tx = signing_session::SigningSession::read(&path)?.to_transaction()?;
+                    (tx.message, tx.signatures)
+                }
+                (None, Some(transaction)) => match cli
+                    .encoding
+                    .unwrap_or(Encoding::Base64)
+                    .decode_signed(&transaction)?
+                {
+                    (message, None) => match signature_dir {
+                        Some(dir) => {
+                            let signatures = read_signature_dir(&dir, &message)?;
+                            (message, signatures)
+                        }
+                        None => (message, signatures),
+                    },
+                    (message, Some(assembled))
+                        if signatures.is_empty() && signature_dir.is_none() =>
+                    {
+                        (message, assembled)
+                    }
+                    (_, Some(_)) => {
+                        return Err(SerumError::Validation(
+                            "the transaction already holds its signatures, drop --signatures"
+                                .to_string(),
+                        )
+                        .into())
+                    }
+                },
+                (None, None) => {
+                    unreachable!("clap requires the transaction, the session or the QR chunks")
+                }
+            };
+            if require_confirmed_nonce {
+                if cli.offline {
+                    return Err(anyhow::Error::msg(
+                        "--require-confirmed-nonce reads the nonce account and can't be used offline",
+                    ));
+                }
+                let nonce_account = durable_nonce_account(&message).ok_or_else(|| {
+                    SerumError::Validation(
+                        "--require-confirmed-nonce needs a durable-nonce transaction".to_string(),
+                    )
+                })?;
+                log::info!("fetching finalized nonce account {}", nonce_account);
+                let rpc = program.async_rpc();
+                let account = rpc::retry(|| {
+                    nonce_utils::nonblocking::get_account_with_commitment(
+                        &rpc,
+                        &nonce_account,
+                        CommitmentConfig::finalized(),
+                    )
+                })
+                .await?;
+                let finalized = nonce_utils::nonblocking::data_from_account(&account)?.blockhash();
+                if finalized != message.recent_blockhash {
+                    return Err(SerumError::Validation(format!(
+                        "the finalized nonce of {} is {}, but the transaction uses {}; not submitting",
+                        nonce_account, finalized, message.recent_blockhash
+                    ))
+                    .into());
+                }
+            }
+            if !cli.offline {
+                if let Some(nonce_account) = durable_nonce_account(&message) {
+                    let current = current_nonce(&program, &nonce_account).await?;
+                    if current != message.recent_blockhash {
+                        caution(&format!(
+                            "nonce account {} has advanced to {}, but the transaction uses {} and will fail",
+                            nonce_account, current, message.recent_blockhash
+                        ))?;
+                        if !force {
+                            return Err(anyhow::Error::msg(
+                                "the nonce has advanced, rebuild the transaction or pass --force to submit anyway",
+                            ));
+                        }
+                    }
+                } else if (cli.rpc_retries == 0 || sole_local_signer(&output, &message).is_none())
+                    && !blockhash_is_valid(&program, &message.recent_blockhash).await?
+                {
+                    caution(&format!(
+                        "the blockhash {} of the transaction has expired and the cluster will reject it",
+                        message.recent_blockhash
+                    ))?;
+                    if !force {
+                        return Err(anyhow::Error::msg(
+                            "the blockhash expired, rebuild the transaction with a fresh blockhash, or with --nonce-account to give signers more time, and sign it again; or pass --force to submit anyway",
+                        ));
+                    }
+                }
+            }
+            check_signature_count(&message, signatures.len())?;
             let tx = Transaction {
                 signatures,
                 message,
             };
-            let sig = program
-                .async_rpc()
-                .send_and_confirm_transaction(&tx)
-                .await?;
+            if !skip_verify {
+                verify_signatures(&tx)?;
+            }
+            let sig = if stream_logs {
+                submit_streaming_logs(&program, &output, tx, cli.rpc_retries, cluster.ws_url())
+                    .await?
+            } else {
+                submit(&program, &output, tx, cli.rpc_retries).await?
+            };
             println!("Transaction submitted: {}", style(sig).green());
+            println!("{}", cli.explorer.transaction_url(&sig, &cluster));
+            if let Some(min_confirmations) = min_confirmations {
+                wait_for_confirmations(&program, &sig, min_confirmations as usize).await?;
+            }
         }
-    }
-    Ok(())
+        Command::AddSignatures { file, signatures } => {
+            let mut session = signing_session::SigningSession::read(&file)?;
+            for signature in signatures {
+                let signer = session.add_signature(signature)?;
+                println!("Signature of {} added", style(signer).green());
+            }
+            session.write(&file)?;
+            let pending = session.pending();
+            if pending.is_empty() {
+                println!(
+                    "{}",
+                    style("The session is complete, submit it with Submit --from-session").green()
+                );
+            } else {
+                println!("Signatures still missing:");
+                for signer in pending {
+                    println!("  {}", signer);
+                }
+            }
+        }
+        Command::SubmitBundle { file, endpoint } => {
+            let endpoint = match endpoint {
+                Some(endpoint) => endpoint,
+                None => bundle::default_endpoint(mainnet)
+                    .ok_or_else(|| {
+                        anyhow::Error::msg(format!(
+                            "no block engine is known for {}, pass its bundle endpoint with --endpoint",
+                            cluster
+                        ))
+                    })?
+                    .to_string(),
+            };
+            // The cluster guard only ran for a mainnet cluster.
+            if endpoint == bundle::MAINNET_ENDPOINT && !mainnet && !cli.mainnet_confirm {
+                confirm_mainnet()?;
+            }
+            let content = std::fs::read_to_string(&file).map_err(|e| {
+                anyhow::Error::msg(format!("failed to read bundle {}: {}", file.display(), e))
+            })?;
+            let bundle: Vec<SignedTransaction> = serde_json::from_str(&content)?;
+            let transactions = bundle
+                .iter()
+                .map(SignedTransaction::to_transaction)
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            bundle::check_tip(&transactions)?;
+            let id = bundle::send_bundle(&endpoint, &transactions).await?;
+            println!("Bundle submitted: {}", style(id).green());
+        }
+        Command::SubmitBatch { file, concurrency } => {
+            let content = std::fs::read_to_string(&file).map_err(|e| {
+                anyhow::Error::msg(format!("failed to read batch {}: {}", file.display(), e))
+            })?;
+            let batch: Vec<SignedTransaction> = serde_json::from_str(&content)?;
+            let (program, output, retries) = (&program, &output, cli.rpc_retries);
+            let results: Vec<anyhow::Result<Signature>> =
+                futures::stream::iter(batch.iter().map(|signed| async move {
+                    submit(program, output, signed.to_transaction()?, retries).await
+                }))
+                .buffered(concurrency as usize)
+                .collect()
+                .await;
+            let mut failed = 0;
+            for (i, result) in results.iter().enumerate() {
+                match result {
+                    Ok(sig) => println!(
+                        "#{}: submitted {}\n    {}",
+                        i,
+                        style(sig).green(),
+                        cli.explorer.transaction_url(sig, &cluster)
+                    ),
+                    Err(e) => {
+                        failed += 1;
+                        println!("#{}: {} {:#}", i, style("failed").red(), e);
+                    }
+                }
+            }
+            println!(
+                "{} of {} transactions submitted",
+                results.len() - failed,
+                results.len()
+            );
+            if failed > 0 {
+                return Err(anyhow::Error::msg(format!(
+                    "{} transactions failed",
+                    failed
+                )));
+            }
+        }
+        Command::SignOffchain { message, domain } => {
+            let signer = output
+                .local_signer
+                .as_ref()
+                .ok_or(anyhow::Error::msg("private-key is required"))?;
+            if domain.is_none() {
+                offchain::check_domainless(&message)
+                    .map_err(|e| SerumError::Validation(e.to_string()))?;
+            }
+            let text = offchain_text(domain.as_deref(), &message);
+            let sig = OffchainMessage::new(0, text.as_bytes())?.sign(signer)?;
+            println!("Signer: {}", style(signer.pubkey()).green());
+            if let Some(domain) = domain {
+                println!("Domain: {}", style(domain).green());
+            }
+            println!("Message signed: {}", style(sig).green());
+        }
+        Command::VerifyOffchain {
+            signer,
+            message,
+            signature,
+            domain,
+        } => {
+            let text = offchain_text(domain.as_deref(), &message);
+            if !OffchainMessage::new(0, text.as_bytes())?.verify(&signer, &signature)? {
+                return Err(anyhow::Error::msg(match domain {
+                    Some(domain) => format!(
+                        "signature doesn't match the message and signer in domain {}",
+                        domain
+                    ),
+                    None => "signature doesn't match the message and signer".to_string(),
+                }));
+            }
+            println!("Signature is valid for signer {}", style(signer).green());
+        }
+        Command::AttestProposal {
+            transaction,
+            output: path,
+        } => {
+            if cli.offline {
+                return Err(anyhow::Error::msg(
+                    "attesting a proposal reads it from the cluster and can't be done offline",
+                ));
+            }
+            let signer = output
+                .local_signer
+                .as_ref()
+                .ok_or(anyhow::Error::msg("private-key is required"))?;
+            let transaction_account = fetch_checked_transaction(&program, transaction).await?;
+            load_multisig(&program, transaction_account.multisig).await?;
+            check_proposer(&program, &transaction, &signer.pubkey()).await?;
+            let attestation = attestation::Attestation::new(
+                &transaction,
+                &transaction_account,
+                &pid,
+                &signer.pubkey(),
+                |message| Ok(message.sign(signer)?),
+            )?;
+            let path = path.unwrap_or_else(|| attestation.file_name().into());
+            std::fs::write(&path, serde_json::to_string_pretty(&attestation)?)?;
+            println!(
+                "Attestation of {} written to {}",
+                style(transaction).green(),
+                style(path.display()).green()
+            );
+        }
+        Command::VerifyAttestation { file } => {
+            let content = std::fs::read_to_string(&file).map_err(|e| {
+                anyhow::Error::msg(format!(
+                    "failed to read attestation {}: {}",
+                    file.display(),
+                    e
+                ))
+            })?;
+            let attestation: attestation::Attestation = serde_json::from_str(&content)?;
+            attestation.verify()?;
+            println!(
+                "Attestation signed by {}",
+                style(&attestation.proposer).green()
+            );
+            if !cli.offline {
+                let transaction: AnchorPubkey = attestation.transaction.parse()?;
+                let transaction_account = fetch_checked_transaction(&program, transaction).await?;
+                if !attestation.matches(&transaction_account) {
+                    return Err(SerumError::Validation(format!(
+                        "transaction {} doesn't hold the attested proposal",
+                        transaction
+                    ))
+                    .into());
+                }
+                load_multisig(&program, transaction_account.multisig).await?;
+                check_proposer(&program, &transaction, &attestation.proposer.parse()?).await?;
+                println!(
+                    "{}",
+                    style("The attestation matches the proposal on the cluster").green()
+                );
+            }
+        }
+        Command::PrepareNonces { signers, json } => {
+            let rpc = program.async_rpc();
+            let nonces =
+                futures::future::try_join_all(signers.iter().map(|(signer, nonce_account)| {
+                    let rpc = &rpc;
+                    async move {
+                        log::info!("fetching nonce account {}", nonce_account);
+                        let account = rpc::retry(|| {
+                            nonce_utils::nonblocking::get_account(rpc, nonce_account)
+                        })
+                        .await?;
+                        let data = nonce_utils::nonblocking::data_from_account(&account)?;
+                        if data.authority != *signer {
+                            return Err(anyhow::Error::msg(format!(
+                                "nonce account {} is authorized by {}, not by {}",
+                                nonce_account, data.authority, signer
+                            )));
+                        }
+                        Ok((*signer, *nonce_account, data.blockhash()))
+                    }
+                }))
+                .await?;
+            if json {
+                let nonces: Vec<_> = nonces
+                    .iter()
+                    .map(|(signer, nonce_account, nonce)| {
+                        serde_json::json!({
+                            "signer": signer.to_string(),
+                            "nonce_account": nonce_account.to_string(),
+                            "nonce": nonce.to_string(),
+                        })
+                    })
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&nonces)?);
+            } else {
+                for (signer, nonce_account, nonce) in &nonces {
+                    println!("{}:{}:{}", signer, nonce_account, style(nonce).green());
+                }
+            }
+        }
+        Command::CloseNonceAccount {
+            signer,
+            account,
+            authority,
+            destination,
+            lamports,
+        } => {
+            if account == signer.nonce_account()? {
+                return Err(SerumError::Validation(format!(
+                    "{} is the nonce account the message itself advances, close it with a message using another nonce account",
+                    account
+                ))
+                .into());
+            }
+            let lamports = match lamports {
+                Some(lamports) => lamports,
+                None if cli.offline => {
+                    return Err(anyhow::Error::msg("lamports are required in offline mode"))
+                }
+                None => {
+                    log::info!("fetching balance of {}", account);
+                    let rpc = program.async_rpc();
+                    rpc::retry(|| rpc.get_balance(&account)).await?
+                }
+            };
+            println!(
+                "{}",
+                style("Closing a nonce account with the following parameters:").bold()
+            );
+            println!(
+                "Nonce account: {}\nDestination: {}\nAmount: {}\n",
+                style(account).green(),
+                style(destination).green(),
+                style(decode::format_lamports(lamports)).green(),
+            );
+            warn("transactions using this nonce account can never be submitted once it is closed");
+
+            let withdraw = system_instruction::withdraw_nonce_account(
+                &account,
+                &authority.unwrap_or(signer.signer),
+                &destination,
+                lamports,
+            );
+            let tx = build_tx(
+                signer.signer,
+                signer.nonce()?,
+                signer.nonce_account()?,
+                vec![withdraw],
+            )?;
+            output.print(&tx, &[])?;
+        }
+        Command::SetNonceAuthority {
+            signer,
+            account,
+            authority,
+            new_authority,
+        } => {
+            let authority = authority.unwrap_or(signer.signer);
+            if !cli.offline {
+                log::info!("fetching nonce account {}", account);
+                let rpc = program.async_rpc();
+                let nonce_account =
+                    rpc::retry(|| nonce_utils::nonblocking::get_account(&rpc, &account)).await?;
+                let current =
+                    nonce_utils::nonblocking::data_from_account(&nonce_account)?.authority;
+                if current != authority {
+                    return Err(SerumError::Validation(format!(
+                        "nonce account {} is authorized by {}, not by {}",
+                        account, current, authority
+                    ))
+                    .into());
+                }
+            }
+            println!(
+                "{}",
+                style("Changing the authority of a nonce account with the following parameters:")
+                    .bold()
+            );
+            println!(
+                "Nonce account: {}\nCurrent authority: {}\nNew authority: {}\n",
+                style(account).green(),
+                style(authority).green(),
+                style(new_authority).green(),
+            );
+            warn("transactions already signed by the current authority can still be submitted until the nonce advances or this change lands");
+
+            let authorize =
+                system_instruction::authorize_nonce_account(&account, &authority, &new_authority);
+            let tx = build_tx(
+                signer.signer,
+                signer.nonce()?,
+                signer.nonce_account()?,
+                vec![authorize],
+            )?;
+            output.print(&tx, &[])?;
+        }
+        Command::ApproveWithKeys {
+            multisig,
+            transaction,
+            owner_keys,
+        } => {
+            if cli.offline {
+                return Err(anyhow::Error::msg(
+                    "approving with several keys reads the nonces and can't be done offline",
+                ));
+            }
+            let multisig_account = load_multisig(&program, multisig).await?;
+            log::info!("fetching transaction account {}", transaction);
+            let transaction_account: coral_multisig::Transaction =
+                rpc::retry(|| program.account(transaction)).await?;
+            check_owner_set(&multisig_account, &transaction_account)?;
+
+            let mut approvals = Vec::new();
+            for (key_file, nonce_account) in owner_keys {
+                let keypair = read_keypair(&key_file, cli.keypair_format)?;
+                let owner = keypair.pubkey();
+                let index = multisig_account
+                    .owners
+                    .iter()
+                    .position(|o| *o == owner)
+                    .ok_or_else(|| {
+                        anyhow::Error::msg(format!("{} is not an owner of the multisig", owner))
+                    })?;
+                if transaction_account
+                    .signers
+                    .get(index)
+                    .copied()
+                    .unwrap_or(false)
+                {
+                    warn(&format!("{} has already approved, skipping", owner));
+                    continue;
+                }
+                let signer = SignerArg::new(
+                    owner,
+                    nonce_account,
+                    current_nonce(&program, &nonce_account).await?,
+                );
+                let tx = approve_message(&program, &signer, multisig, transaction)?;
+                let sig = keypair.sign_message(&tx.serialize());
+                approvals.push(SignedTransaction {
+                    transaction: BASE64_STANDARD.encode(tx.serialize()),
+                    signatures: vec![sig.to_string()],
+                    signers: Vec::new(),
+                });
+            }
+            println!("{}", serde_json::to_string_pretty(&approvals)?);
+        }
+        Command::ShowTransaction {
+            transaction,
+            idl_file,
+            ..
+        } => {
+            let idl = idl_file
+                .map(|path| idl::Idl::from_file(&path, &pid))
+                .transpose()?;
+            log::info!("fetching transaction account {}", transaction);
+            let transaction_account: coral_multisig::Transaction =
+                rpc::retry(|| program.account(transaction)).await?;
+            let multisig_account = load_multisig(&program, transaction_account.multisig).await?;
+
+            println!(
+                "Multisig address: {}",
+                style(transaction_account.multisig).green()
+            );
+            println!("Program: {}", style(transaction_account.program_id).green());
+            println!("Accounts:");
+            let roles =
+                decode::account_roles(&transaction_account.program_id, &transaction_account.data);
+            for (position, acc) in transaction_account.accounts.iter().enumerate() {
+                let mut flags = Vec::new();
+                if acc.is_signer {
+                    flags.push("signer");
+                }
+                if acc.is_writable {
+                    flags.push("writable");
+                }
+                println!(
+                    "  {} {:?}{}",
+                    acc.pubkey,
+                    flags,
+                    decode::role_label(roles, position)
+                );
+            }
+            println!(
+                "Data ({} bytes): {}",
+                transaction_account.data.len(),
+                decode::hex(&transaction_account.data)
+            );
+            let decoded = idl
+                .as_ref()
+                .filter(|_| transaction_account.program_id == pid)
+                .and_then(|idl| idl.decode_instruction(&transaction_account.data))
+                .transpose()?;
+            let description = match decoded {
+                Some((name, args)) => format!("{} {}", name, args),
+                None => decode::describe_instruction(
+                    &transaction_account.program_id,
+                    &pid,
+                    &transaction_account.data,
+                ),
+            };
+            println!("Decoded: {}", style(description).bold());
+            let accounts: Vec<AnchorPubkey> = transaction_account
+                .accounts
+                .iter()
+                .map(|acc| acc.pubkey)
+                .collect();
+            decode::print_dangers(&decode::dangers(
+                &transaction_account.program_id,
+                &pid,
+                &accounts,
+                &transaction_account.data,
+                &decode::DangerContext {
+                    multisig_signer: Some(
+                        derive_multisig_signer(&transaction_account.multisig, &pid).0,
+                    ),
+                    owners: Some(&multisig_account.owners),
+                },
+            ));
+            println!("Executed: {}", transaction_account.did_execute);
+            println!("Approvals:");
+            for (owner, signed) in multisig_account
+                .owners
+                .iter()
+                .zip(&transaction_account.signers)
+            {
+                println!(
+                    "  {} {}",
+                    owner,
+                    if *signed {
+                        style("approved").green()
+                    } else {
+                        style("pending").yellow()
+                    }
+                );
+            }
+            if transaction_account.owner_set_seqno != multisig_account.owner_set_seqno {
+                caution(
+                    "the owners changed since the proposal, the transaction can't be executed",
+                )?;
+            }
+        }
+        Command::ListTransactions {
+            multisig,
+            since_slot,
+            limit,
+        } => {
+            let multisig_account = load_multisig(&program, multisig).await?;
+            log::info!("fetching the transactions of multisig {}", multisig);
+            // The multisig address follows the discriminator.
+            let filters = vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                8,
+                multisig.as_ref(),
+            ))];
+            let mut transactions: Vec<(AnchorPubkey, coral_multisig::Transaction)> =
+                rpc::retry(|| program.accounts(filters.clone())).await?;
+            transactions.sort_by_key(|(address, _)| *address);
+            // The creations are looked up at once, the limit only applies to
+            // the transactions they keep.
+            let slots = if since_slot.is_some() {
+                futures::future::try_join_all(
+                    transactions
+                        .iter()
+                        .map(|(address, _)| creation_slot(&program, address)),
+                )
+                .await?
+            } else {
+                vec![None; transactions.len()]
+            };
+            let mut listed = Vec::new();
+            for ((address, transaction), slot) in transactions.into_iter().zip(slots) {
+                let created = match since_slot {
+                    Some(since_slot) => {
+                        // Creations pruned from the node's history are older
+                        // than any slot it can tell.
+                        let Some(slot) = slot else {
+                            log::info!(
+                                "skipping {}, its creation is no longer in the history of the RPC node",
+                                address
+                            );
+                            continue;
+                        };
+                        if slot < since_slot {
+                            continue;
+                        }
+                        Some(slot)
+                    }
+                    None => None,
+                };
+                listed.push((address, transaction, created));
+                if limit.is_some_and(|limit| listed.len() >= limit) {
+                    break;
+                }
+            }
+            for (address, transaction, created) in &listed {
+                let approvals = ApprovalCount::new(&multisig_account, transaction);
+                let status = if transaction.did_execute {
+                    style("executed").green()
+                } else if transaction.owner_set_seqno != multisig_account.owner_set_seqno {
+                    style("stale").red()
+                } else {
+                    style("pending").yellow()
+                };
+                print!(
+                    "{} {} {} of {} approvals",
+                    address, status, approvals.approved, approvals.threshold
+                );
+                match created {
+                    Some(slot) => println!(", created in slot {}", slot),
+                    None => println!(),
+                }
+            }
+            println!("{} transactions", listed.len());
+        }
+        Command::ListAssets {
+            multisig,
+            mint,
+            min_balance,
+        } => {
+            let mint = mint.map(|mint| mint.resolve(mainnet)).transpose()?;
+            if cli.offline {
+                return Err(anyhow::Error::msg(
+                    "listing assets reads them from the cluster and can't be done offline",
+                ));
+            }
+            let (multisig_pda, _) = derive_multisig_signer(&multisig, &pid);
+            // Filters are rebuilt for each retry, they can't be cloned.
+            let filters: Vec<(fn(AnchorPubkey) -> TokenAccountsFilter, AnchorPubkey)> = match mint {
+                Some(mint) => vec![(TokenAccountsFilter::Mint, mint)],
+                None => vec![
+                    (
+                        TokenAccountsFilter::ProgramId,
+                        spl_token::id().to_bytes().into(),
+                    ),
+                    (TokenAccountsFilter::ProgramId, spl_token_2022::id()),
+                ],
+            };
+            let rpc = program.async_rpc();
+            let mut assets = Vec::new();
+            for (filter, key) in filters {
+                log::info!("fetching the token accounts of {}", multisig_pda);
+                for keyed in
+                    rpc::retry(|| rpc.get_token_accounts_by_owner(&multisig_pda, filter(key)))
+                        .await?
+                {
+                    let UiAccountData::Json(parsed) = keyed.account.data else {
+                        return Err(anyhow::Error::msg(format!(
+                            "token account {} isn't returned parsed",
+                            keyed.pubkey
+                        )));
+                    };
+                    let account: UiTokenAccount =
+                        serde_json::from_value(parsed.parsed["info"].clone())?;
+                    assets.push((keyed.pubkey, keyed.account.owner, account));
+                }
+            }
+            assets.sort_by(|(a, _, a_account), (b, _, b_account)| {
+                (&a_account.mint, a).cmp(&(&b_account.mint, b))
+            });
+
+            println!(
+                "Token accounts of multisig signer {}:",
+                style(multisig_pda).green()
+            );
+            let mut listed = 0;
+            for (address, token_program, account) in assets {
+                let amount: u64 = account.token_amount.amount.parse()?;
+                let decimals = account.token_amount.decimals;
+                if min_balance
+                    .is_some_and(|min| (amount as f64) < min * 10f64.powi(decimals as i32))
+                {
+                    continue;
+                }
+                let program_name = if token_program == spl_token_2022::id().to_string() {
+                    "Token-2022"
+                } else {
+                    "Token"
+                };
+                println!(
+                    "{} mint {} balance {} {}",
+                    address,
+                    account.mint,
+                    style(decode::format_amount(amount, decimals)).green(),
+                    program_name
+                );
+                listed += 1;
+            }
+            println!("{} token accounts", listed);
+        }
+        Command::VerifyExecution {
+            signature,
+            transaction,
+            ..
+        } => {
+            let rpc = program.async_rpc();
+            let config = confirmed_transaction_config(&rpc);
+            log::info!("fetching confirmed transaction {}", signature);
+            let confirmed =
+                rpc::retry(|| rpc.get_transaction_with_config(&signature, config)).await?;
+            if let Some(err) = confirmed
+                .transaction
+                .meta
+                .as_ref()
+                .and_then(|meta| meta.err.clone())
+            {
+                return Err(anyhow::Error::msg(format!(
+                    "transaction {} failed: {}",
+                    signature, err
+                )));
+            }
+            let tx = confirmed
+                .transaction
+                .transaction
+                .decode()
+                .ok_or(anyhow::Error::msg("can't decode the confirmed transaction"))?;
+            let keys = tx.message.static_account_keys();
+            let key = |index: u8| keys.get(index as usize);
+            let executes = tx.message.instructions().iter().any(|ix| {
+                key(ix.program_id_index) == Some(&pid)
+                    && decode::multisig_instruction_name(&ix.data) == Some("execute_transaction")
+                    && ix.accounts.get(2).and_then(|&i| key(i)) == Some(&transaction)
+            });
+            if !executes {
+                return Err(anyhow::Error::msg(format!(
+                    "transaction {} doesn't execute {}",
+                    signature, transaction
+                )));
+            }
+            log::info!("fetching transaction account {}", transaction);
+            let transaction_account: coral_multisig::Transaction =
+                rpc::retry(|| program.account(transaction)).await?;
+            if !transaction_account.did_execute {
+                return Err(anyhow::Error::msg(format!(
+                    "transaction account {} is not marked as executed",
+                    transaction
+                )));
+            }
+            println!(
+                "Execution of {} verified in slot {}",
+                style(transaction).green(),
+                style(confirmed.slot).green()
+            );
+        }
+        Command::ValidateProposal { transaction } => {
+            if cli.offline {
+                return Err(anyhow::Error::msg(
+                    "validating a proposal reads it from the cluster and can't be done offline",
+                ));
+            }
+            let transaction_account = fetch_checked_transaction(&program, transaction).await?;
+            let multisig_account = load_multisig(&program, transaction_account.multisig).await?;
+            println!(
+                "Multisig address: {}",
+                style(transaction_account.multisig).green()
+            );
+            println!(
+                "Owner set: {} at the proposal, {} now",
+                transaction_account.owner_set_seqno, multisig_account.owner_set_seqno
+            );
+            if transaction_account.did_execute {
+                return Err(SerumError::Validation(
+                    "the transaction has already been executed".to_string(),
+                )
+                .into());
+            }
+            // A changed owner set explains any mismatch of the approvals.
+            if transaction_account.owner_set_seqno != multisig_account.owner_set_seqno {
+                caution(
+                    "the owner set changed since the proposal: the approvals gathered so far refer to the previous owners and the transaction can't be executed, propose it again",
+                )?;
+            } else if transaction_account.signers.len() != multisig_account.owners.len() {
+                return Err(SerumError::Validation(format!(
+                    "the transaction tracks approvals of {} owners, but the multisig has {}",
+                    transaction_account.signers.len(),
+                    multisig_account.owners.len()
+                ))
+                .into());
+            } else {
+                let approvals = ApprovalCount::new(&multisig_account, &transaction_account);
+                println!(
+                    "{} ({} of {} approvals)",
+                    style("The proposal is consistent with the current owners").green(),
+                    approvals.approved,
+                    approvals.threshold
+                );
+            }
+        }
+        Command::TransactionStatus {
+            transaction, json, ..
+        } => {
+            let approvals = fetch_approval_count(&program, transaction).await?;
+            if json {
+                println!("{}", serde_json::to_string(&approvals)?);
+            } else {
+                println!(
+                    "Approvals: {} of {} required",
+                    style(approvals.approved).green(),
+                    style(approvals.threshold).green(),
+                );
+            }
+        }
+        Command::Decode {
+            transaction,
+            from_qr,
+            idl,
+            idl_file,
+        } => {
+            let transaction = match (from_qr, transaction) {
+                (Some(path), _) => qr::read(&path)?,
+                (None, Some(transaction)) => transaction,
+                (None, None) => unreachable!("clap requires the transaction or the QR chunks"),
+            };
+            let message = cli
+                .encoding
+                .unwrap_or(Encoding::Base64)
+                .decode_message(&transaction)?;
+            let idl = match idl_file {
+                Some(path) => Some(idl::Idl::from_file(&path, &pid)?),
+                None if idl => Some(idl::Idl::fetch(&program, &pid).await?),
+                None => None,
+            };
+            if let Some(idl) = idl {
+                let json = decode::instructions_json(&message, &pid, &idl)?;
+                println!("{}", serde_json::to_string_pretty(&json)?);
+            } else {
+                decode::print_message(&message, &pid);
+            }
+            if cli.print_message_hash {
+                println!(
+                    "Message hash (SHA-256): {}",
+                    style(message_hash(&message)).green()
+                );
+            }
+        }
+        Command::DumpMessageFields { transaction } => {
+            let message = cli
+                .encoding
+                .unwrap_or(Encoding::Base64)
+                .decode_message(&transaction)?;
+            decode::dump_message_fields(&message);
+        }
+        Command::RebuildMessage {
+            manifest: path,
+            transaction,
+        } => {
+            let request = manifest::SigningRequest::read(&path)?;
+            let rebuilt = request.rebuild()?;
+            let distributed = match transaction {
+                Some(transaction) => cli
+                    .encoding
+                    .unwrap_or(Encoding::Base64)
+                    .decode_message(&transaction)?,
+                None => request.message()?,
+            };
+            if rebuilt.serialize() != distributed.serialize() {
+                println!(
+                    "{}",
+                    style("The distributed message differs from the rebuilt one:").red()
+                );
+                decode::compare_messages(&rebuilt, &distributed, &pid);
+                return Err(SerumError::Validation(
+                    "the distributed message doesn't match the instructions of the manifest"
+                        .to_string(),
+                )
+                .into());
+            }
+            println!(
+                "{}",
+                style("The distributed message matches the rebuilt one").green()
+            );
+            println!(
+                "Message hash (SHA-256): {}",
+                style(message_hash(&rebuilt)).green()
+            );
+        }
+        Command::Compare { old, new } => {
+            let (old, new) = (read_message_arg(&old)?, read_message_arg(&new)?);
+            if !decode::compare_messages(&old, &new, &pid) {
+                println!("{}", style("Messages are identical").green());
+            }
+        }
+        Command::VerifyProgram { expect_hash } => {
+            let rpc = program.async_rpc();
+            log::info!("fetching program account {}", pid);
+            let account = rpc::retry(|| rpc.get_account(&pid)).await?;
+            let bytecode = if bpf_loader_upgradeable::check_id(&account.owner) {
+                let UpgradeableLoaderState::Program {
+                    programdata_address,
+                } = bincode::deserialize(&account.data)?
+                else {
+                    return Err(anyhow::Error::msg(format!(
+                        "{} is not an upgradeable program account",
+                        pid
+                    )));
+                };
+                log::info!("fetching program data account {}", programdata_address);
+                let data = rpc::retry(|| rpc.get_account_data(&programdata_address)).await?;
+                if let UpgradeableLoaderState::ProgramData {
+                    slot,
+                    upgrade_authority_address,
+                } = bincode::deserialize(&data)?
+                {
+                    println!("Last deployed in slot: {}", slot);
+                    match upgrade_authority_address {
+                        Some(authority) => {
+                            println!("Upgrade authority: {}", style(authority).yellow())
+                        }
+                        None => println!("Upgrade authority: none, the program is immutable"),
+                    }
+                }
+                data.get(UpgradeableLoaderState::size_of_programdata_metadata()..)
+                    .ok_or_else(|| {
+                        SerumError::Validation(format!(
+                            "program data account {} is {} bytes, shorter than its metadata",
+                            programdata_address,
+                            data.len()
+                        ))
+                    })?
+                    .to_vec()
+            } else {
+                account.data
+            };
+            let len = bytecode
+                .iter()
+                .rposition(|&byte| byte != 0)
+                .map_or(0, |last| last + 1);
+            let program_hash = decode::hex(hash::hash(&bytecode[..len]).as_ref());
+            println!("Program hash: {}", style(&program_hash).green());
+            if let Some(expected) = expect_hash {
+                if !program_hash.eq_ignore_ascii_case(expected.trim()) {
+                    return Err(anyhow::Error::msg(format!(
+                        "the deployed program {} is NOT the expected build: its hash is {}, expected {}; do not use it until the upgrade is explained",
+                        pid, program_hash, expected
+                    )));
+                }
+                println!(
+                    "{}",
+                    style("The deployed program matches the expected hash").green()
+                );
+            }
+        }
+        Command::ExportReview {
+            transaction,
+            format,
+            output: path,
+        } => {
+            let message = read_message_arg(&transaction)?;
+            let mut transfer_decimals = HashMap::new();
+            if !cli.offline {
+                for (i, ix) in message.instructions.iter().enumerate() {
+                    let program_id = message.account_keys[ix.program_id_index as usize];
+                    // Transfers proposed to the multisig are reviewed along
+                    // with the proposal.
+                    let (program_id, source, data) = if program_id == pid {
+                        match decode::proposed_instruction(&ix.data) {
+                            Some((program_id, accounts, data)) => {
+                                (program_id, accounts.first().map(|acc| acc.pubkey), data)
+                            }
+                            None => continue,
+                        }
+                    } else {
+                        let source = ix
+                            .accounts
+                            .first()
+                            .map(|&index| message.account_keys[index as usize]);
+                        (program_id, source, ix.data.clone())
+                    };
+                    if program_id.to_bytes() != spl_token::id().to_bytes() {
+                        continue;
+                    }
+                    if let (Ok(TokenInstruction::Transfer { .. }), Some(source)) =
+                        (TokenInstruction::unpack(&data), source)
+                    {
+                        let account =
+                            fetch_token_account(&program, &source, "source", &mut mint_decimals)
+                                .await?;
+                        transfer_decimals
+                            .insert(i, mint_decimals.observe(account.mint, account.decimals)?);
+                    }
+                }
+            }
+            let document = review::render(&message, &pid, format, &transfer_decimals)?;
+            match path {
+                Some(path) => {
+                    std::fs::write(&path, document)?;
+                    println!("Review written to {}", path.display());
+                }
+                None => print!("{}", document),
+            }
+        }
+        Command::GenerateKeypair { outfile, force, .. } => {
+            let keypair = Keypair::new();
+            match outfile {
+                Some(path) => {
+                    if path.exists() && !force {
+                        return Err(anyhow::Error::msg(format!(
+                            "{} already exists, pass --force to overwrite it",
+                            path.display()
+                        )));
+                    }
+                    write_keypair_file(&keypair, &path).map_err(|e| {
+                        anyhow::Error::msg(format!("failed to write {}: {}", path.display(), e))
+                    })?;
+                    println!("Keypair written to {}", path.display());
+                }
+                None => {
+                    warn("the secret key is only printed, anyone seeing it controls the account");
+                    println!("Secret key: {}", keypair.to_base58_string());
+                }
+            }
+            println!("Pubkey: {}", style(keypair.pubkey()).green());
+        }
+        Command::Plan {
+            multisig,
+            action,
+            proposer,
+        } => {
+            if cli.offline {
+                return Err(anyhow::Error::msg(
+                    "the plan reads the owners and threshold of the multisig and can't be made offline",
+                ));
+            }
+            let multisig_account = load_multisig(&program, multisig).await?;
+            if let Some(proposer) = proposer {
+                if !multisig_account.owners.contains(&proposer) {
+                    return Err(SerumError::Validation(format!(
+                        "{} is not an owner of the multisig and can't propose",
+                        proposer
+                    ))
+                    .into());
+                }
+            }
+            let transaction_rent =
+                rent_exemption(&program, cli.offline, TRANSACTION_ACCOUNT_SIZE).await?;
+            plan::print(
+                action,
+                &plan::Multisig {
+                    address: multisig,
+                    owners: &multisig_account.owners,
+                    threshold: multisig_account.threshold,
+                },
+                proposer,
+                transaction_rent,
+            );
+        }
+        Command::Rent { size } => {
+            let sizes = size.map(|size| ("Account", size)).into_iter().chain([
+                ("Multisig account", MULTISIG_ACCOUNT_SIZE),
+                ("Transaction account", TRANSACTION_ACCOUNT_SIZE),
+            ]);
+            for (name, size) in sizes {
+                let lamports = rent_exemption(&program, cli.offline, size).await?;
+                println!(
+                    "{} ({} bytes): {}",
+                    name,
+                    size,
+                    style(decode::format_lamports(lamports)).green(),
+                );
+            }
+        }
+        Command::Fixture { seed } => {
+            let fixture = fixture::generate(seed, &pid)?;
+            println!("{}", serde_json::to_string_pretty(&fixture)?);
+        }
+        Command::SelfTest => {
+            if !self_test::is_local(&cluster) {
+                return Err(anyhow::Error::msg(
+                    "the self-test airdrops funds and only runs against a local validator, pass --cluster localhost",
+                ));
+            }
+            self_test::run(&program).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Token account fields the transfer commands rely on.
+struct TokenAccountInfo {
+    /// Token program owning the account, Token or Token-2022.
+    program_id: AnchorPubkey,
+    mint: AnchorPubkey,
+    /// Balance in base units.
+    amount: u64,
+    decimals: u8,
+    frozen: bool,
+}
+
+/// Fetches a single token account, see `fetch_token_accounts`.
+async fn fetch_token_account(
+    program: &Program<&Keypair>,
+    address: &AnchorPubkey,
+    role: &str,
+    decimals: &mut MintDecimals,
+) -> anyhow::Result<TokenAccountInfo> {
+    log::info!("fetching token account {}", address);
+    let [account]: [Option<TokenAccountInfo>; 1] =
+        fetch_token_accounts(program, &[*address], decimals)
+            .await?
+            .try_into()
+            .map_err(|_| anyhow::Error::msg("expected one token account"))?;
+    account.ok_or_else(|| anyhow::Error::msg(format!("{} token account not found", role)))
+}
+
+/// Transfer of `amount` out of `source` by `authority`: Transfer for the
+/// Token program, and TransferChecked for Token-2022, whose mints may need
+/// extensions checked.
+fn token_transfer(
+    token_program: &AnchorPubkey,
+    mint: &AnchorPubkey,
+    decimals: u8,
+    from: &AnchorPubkey,
+    to: &AnchorPubkey,
+    authority: &AnchorPubkey,
+    amount: u64,
+) -> anyhow::Result<SplInstruction> {
+    if *token_program == spl_token_2022::id() {
+        return Ok(to_spl_instruction(
+            spl_token_2022::instruction::transfer_checked(
+                &spl_token_2022::id(),
+                from,
+                mint,
+                to,
+                authority,
+                &[],
+                amount,
+                decimals,
+            )?,
+        ));
+    }
+    Ok(token_instruction::transfer(
+        &spl_token::id(),
+        &from.to_bytes().into(),
+        &to.to_bytes().into(),
+        &authority.to_bytes().into(),
+        &[],
+        amount,
+    )?)
+}
+
+/// Base fee per signature, for estimating the fee of a message.
+const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+/// Checks that the fee payer can pay the estimated fee of `message` and each
+/// payer of `rents` the rent of the accounts it creates, while keeping the
+/// rent-exempt minimum of its own account, before anyone signs it.
+async fn check_funds(
+    program: &Program<&Keypair>,
+    offline: bool,
+    message: &Message,
+    signer: &SignerArg,
+    rents: &[(AnchorPubkey, u64)],
+) -> anyhow::Result<()> {
+    if offline {
+        return Ok(());
+    }
+    let fee = LAMPORTS_PER_SIGNATURE * message.header.num_required_signatures as u64;
+    // Payers with the fee and the rent they pay.
+    let mut needed = vec![(signer.signer, fee, 0)];
+    for &(payer, rent) in rents {
+        match needed.iter_mut().find(|(needing, _, _)| *needing == payer) {
+            Some((_, _, total)) => *total += rent,
+            None => needed.push((payer, 0, rent)),
+        }
+    }
+    let minimum = rent_exemption(program, offline, 0).await?;
+    let addresses: Vec<_> = needed.iter().map(|(payer, _, _)| *payer).collect();
+    log::info!("fetching balances of the payers");
+    let balances = get_multiple_accounts(program, &addresses).await?;
+    let mut shortfalls = Vec::new();
+    for ((payer, fee, rent), account) in needed.into_iter().zip(balances) {
+        let balance = account.map_or(0, |account| account.lamports);
+        let required = fee + rent + minimum;
+        if balance < required {
+            shortfalls.push(format!(
+                "{} holds {} lamports but needs {} ({} of rent, an estimated fee of {} and the {} it must keep to stay rent exempt), short by {} lamports",
+                payer,
+                balance,
+                required,
+                rent,
+                fee,
+                minimum,
+                required - balance
+            ));
+        }
+    }
+    if shortfalls.is_empty() {
+        return Ok(());
+    }
+    Err(SerumError::InsufficientFunds(shortfalls.join("; ")).into())
+}
+
+/// Warns about accounts missing from the cluster, the usual sign of an
+/// address from another cluster.
+async fn check_accounts_exist(
+    program: &Program<&Keypair>,
+    cluster: &Cluster,
+    accounts: &[(&str, AnchorPubkey)],
+) -> anyhow::Result<()> {
+    if accounts.is_empty() {
+        return Ok(());
+    }
+    let addresses: Vec<_> = accounts.iter().map(|(_, address)| *address).collect();
+    let missing: Vec<_> = accounts
+        .iter()
+        .zip(get_multiple_accounts(program, &addresses).await?)
+        .filter(|(_, account)| account.is_none())
+        .map(|((role, address), _)| format!("{} {}", role, address))
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    caution(&format!(
+        "{} not found on {}; check --cluster, the address may belong to a different cluster",
+        missing.join(", "),
+        cluster
+    ))
+}
+
+/// Accounts a single getMultipleAccounts request returns at most.
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Fetches accounts in concurrent requests of up to `MAX_MULTIPLE_ACCOUNTS`.
+async fn get_multiple_accounts(
+    program: &Program<&Keypair>,
+    addresses: &[AnchorPubkey],
+) -> anyhow::Result<Vec<Option<Account>>> {
+    let rpc = program.async_rpc();
+    let requests = addresses.chunks(MAX_MULTIPLE_ACCOUNTS).map(|chunk| {
+        let rpc = &rpc;
+        async move {
+            log::info!("fetching {} accounts", chunk.len());
+            rpc::retry(|| rpc.get_multiple_accounts(chunk)).await
+        }
+    });
+    Ok(futures::future::try_join_all(requests)
+        .await?
+        .into_iter()
+        .flatten()
+        .collect())
+}
+
+/// Fetches token accounts in as few round trips as possible: one for the
+/// accounts, in concurrent requests of up to `MAX_MULTIPLE_ACCOUNTS`, and one
+/// for the decimals of their mints, skipped for the mints `decimals` already
+/// knows. Addresses holding no token account map to `None`.
+async fn fetch_token_accounts(
+    program: &Program<&Keypair>,
+    addresses: &[AnchorPubkey],
+    decimals: &mut MintDecimals,
+) -> anyhow::Result<Vec<Option<TokenAccountInfo>>> {
+    let states: Vec<_> = get_multiple_accounts(program, addresses)
+        .await?
+        .into_iter()
+        .map(|account| {
+            let account = account.filter(|account| {
+                account.owner.to_bytes() == spl_token::id().to_bytes()
+                    || account.owner == spl_token_2022::id()
+            })?;
+            let state =
+                StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)
+                    .ok()?;
+            Some((account.owner, state.base))
+        })
+        .collect();
+    let mints: Vec<AnchorPubkey> = states
+        .iter()
+        .flatten()
+        .map(|(_, state)| state.mint)
+        .filter(|mint| decimals.get(mint).is_none())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    for (mint, account) in mints
+        .iter()
+        .zip(get_multiple_accounts(program, &mints).await?)
+    {
+        let Some(account) = account else { continue };
+        if let Ok(state) = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&account.data)
+        {
+            decimals.observe(*mint, state.base.decimals)?;
+        }
+    }
+    Ok(states
+        .into_iter()
+        .map(|state| {
+            let (program_id, state) = state?;
+            Some(TokenAccountInfo {
+                program_id,
+                mint: state.mint,
+                amount: state.amount,
+                decimals: decimals.get(&state.mint)?,
+                frozen: state.state == spl_token_2022::state::AccountState::Frozen,
+            })
+        })
+        .collect())
+}
+
+/// Row of a payout CSV.
+struct Payout {
+    recipient: AnchorPubkey,
+    /// Amount in UI units.
+    amount: f64,
+    memo: Option<String>,
+}
+
+/// Reads the `RECIPIENT,AMOUNT[,MEMO]` rows of a payout CSV, skipping a header
+/// row, blank lines and `#` comments. The memo may contain commas.
+fn read_payouts(path: &std::path::Path) -> anyhow::Result<Vec<Payout>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::Error::msg(format!("failed to read {}: {}", path.display(), e)))?;
+    let mut payouts = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |what: String| {
+            anyhow::Error::from(SerumError::Validation(format!(
+                "{} line {}: {}",
+                path.display(),
+                i + 1,
+                what
+            )))
+        };
+        let fields: Vec<&str> = line.splitn(3, ',').map(str::trim).collect();
+        let (recipient, amount, memo) = match fields[..] {
+            [recipient, amount] => (recipient, amount, None),
+            [recipient, amount, memo] => (recipient, amount, Some(memo)),
+            _ => return Err(invalid("expected RECIPIENT,AMOUNT[,MEMO]".to_string())),
+        };
+        if payouts.is_empty() && recipient.eq_ignore_ascii_case("recipient") {
+            continue;
+        }
+        payouts.push(Payout {
+            recipient: recipient
+                .parse()
+                .map_err(|_| invalid(format!("malformed recipient {:?}", recipient)))?,
+            amount: parse_amount(amount).map_err(|e| invalid(e.to_string()))?,
+            memo: memo.filter(|memo| !memo.is_empty()).map(str::to_string),
+        });
+    }
+    if payouts.is_empty() {
+        return Err(SerumError::Validation(format!("{} lists no payouts", path.display())).into());
+    }
+    Ok(payouts)
+}
+
+/// Token transfer to validate before proposing it.
+struct PlannedTransfer {
+    from: AnchorPubkey,
+    to: AnchorPubkey,
+    /// Amount in base units.
+    amount: u64,
+}
+
+/// Validates a batch of transfers with `fetch_token_accounts`, reporting
+/// every failing transfer at once. Transfers from the same source count
+/// against its balance together.
+async fn check_transfers(
+    program: &Program<&Keypair>,
+    transfers: &[PlannedTransfer],
+    decimals: &mut MintDecimals,
+) -> anyhow::Result<()> {
+    let addresses: Vec<AnchorPubkey> = transfers
+        .iter()
+        .flat_map(|transfer| [transfer.from, transfer.to])
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let accounts: HashMap<AnchorPubkey, TokenAccountInfo> = addresses
+        .iter()
+        .copied()
+        .zip(fetch_token_accounts(program, &addresses, decimals).await?)
+        .filter_map(|(address, account)| Some((address, account?)))
+        .collect();
+
+    let mut problems = Vec::new();
+    let mut totals: HashMap<AnchorPubkey, u128> = HashMap::new();
+    for transfer in transfers {
+        let (from, to) = (accounts.get(&transfer.from), accounts.get(&transfer.to));
+        for (role, address, account) in [
+            ("source", transfer.from, from),
+            ("destination", transfer.to, to),
+        ] {
+            match account {
+                None => problems.push(format!("{} {} is not a token account", role, address)),
+                Some(account) if account.frozen => {
+                    problems.push(format!("{} {} is frozen", role, address))
+                }
+                Some(_) => {}
+            }
+        }
+        if let (Some(from), Some(to)) = (from, to) {
+            if from.mint != to.mint {
+                problems.push(format!(
+                    "{} and {} hold different mints",
+                    transfer.from, transfer.to
+                ));
+            }
+        }
+        *totals.entry(transfer.from).or_default() += transfer.amount as u128;
+    }
+    let mut insufficient: Vec<_> = totals
+        .iter()
+        .filter_map(|(source, &total)| {
+            let account = accounts.get(source)?;
+            (total > account.amount as u128).then(|| {
+                format!(
+                    "{} has insufficient balance, holding {} for transfers of {}",
+                    source,
+                    decode::format_amount(account.amount, account.decimals),
+                    decode::format_amount(total as u64, account.decimals)
+                )
+            })
+        })
+        .collect();
+    insufficient.sort();
+    problems.extend(insufficient);
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+    Err(SerumError::Validation(format!(
+        "{} problems with the transfers:\n  {}",
+        problems.len(),
+        problems.join("\n  ")
+    ))
+    .into())
+}
+
+/// Fetches both sides of a transfer, checking that they hold the same token.
+async fn fetch_transfer_accounts(
+    program: &Program<&Keypair>,
+    from: &AnchorPubkey,
+    to: &AnchorPubkey,
+    decimals: &mut MintDecimals,
+) -> anyhow::Result<(TokenAccountInfo, TokenAccountInfo)> {
+    let [from_account, to_account]: [Option<TokenAccountInfo>; 2] =
+        fetch_token_accounts(program, &[*from, *to], decimals)
+            .await?
+            .try_into()
+            .map_err(|_| anyhow::Error::msg("expected two token accounts"))?;
+    let from_account =
+        from_account.ok_or_else(|| anyhow::Error::msg("source token account not found"))?;
+    let to_account =
+        to_account.ok_or_else(|| anyhow::Error::msg("destination token account not found"))?;
+    if from_account.mint != to_account.mint {
+        return Err(anyhow::Error::msg(
+            "source and destination accounts have different mint addresses",
+        ));
+    }
+    for (role, address, account) in [
+        ("source", from, &from_account),
+        ("destination", to, &to_account),
+    ] {
+        if account.frozen {
+            return Err(anyhow::Error::msg(format!(
+                "the {} token account {} is frozen, the transfer would fail",
+                role, address
+            )));
+        }
+    }
+    Ok((from_account, to_account))
+}
+
+/// Resolves the associated token account of `owner` for the mint of the
+/// token account `source`, returning it with the instruction creating it,
+/// funded by `payer`, when it doesn't exist yet.
+async fn destination_ata(
+    program: &Program<&Keypair>,
+    source: &TokenAccountInfo,
+    owner: &AnchorPubkey,
+    payer: &AnchorPubkey,
+) -> anyhow::Result<(AnchorPubkey, Setup)> {
+    // Under the source's token program, which the transfer is built for too.
+    let mint = source.mint;
+    let ata: AnchorPubkey = get_associated_token_address_with_program_id(
+        &owner.to_bytes().into(),
+        &mint.to_bytes().into(),
+        &source.program_id.to_bytes().into(),
+    )
+    .to_bytes()
+    .into();
+
+    let [existing]: [Option<Account>; 1] = get_multiple_accounts(program, &[ata])
+        .await?
+        .try_into()
+        .map_err(|_| anyhow::Error::msg("expected one account"))?;
+    if existing.is_some() {
+        println!(
+            "Destination: associated token account {} of {}",
+            style(ata).green(),
+            style(owner).green()
+        );
+        return Ok((ata, Setup::default()));
+    }
+    let size = if source.program_id == spl_token_2022::id() {
+        // Token-2022 associated token accounts are immutably owned.
+        ExtensionType::try_calculate_account_len::<spl_token_2022::state::Account>(&[
+            ExtensionType::ImmutableOwner,
+        ])?
+    } else {
+        spl_token::state::Account::LEN
+    };
+    let rent = rent_exemption(program, false, size).await?;
+    println!(
+        "Destination associated token account {} of {} doesn't exist, the proposal creates it",
+        style(ata).green(),
+        style(owner).green()
+    );
+    println!(
+        "Rent of the destination account: {}, paid by {}",
+        style(decode::format_lamports(rent)).green(),
+        style(payer).green()
+    );
+    let create = create_associated_token_account_idempotent(
+        &payer.to_bytes().into(),
+        &owner.to_bytes().into(),
+        &mint.to_bytes().into(),
+        &source.program_id.to_bytes().into(),
+    );
+    Ok((
+        ata,
+        Setup {
+            instructions: vec![to_anchor_instruction(create)],
+            rents: vec![(*payer, rent)],
+        },
+    ))
+}
+
+/// Computes the fee a Token-2022 mint withholds from a transfer of `amount`
+/// in the current epoch.
+async fn transfer_fee(
+    program: &Program<&Keypair>,
+    mint: &AnchorPubkey,
+    amount: u64,
+) -> anyhow::Result<u64> {
+    log::info!("fetching mint {}", mint);
+    let rpc = program.async_rpc();
+    let account = rpc::retry(|| rpc.get_account(mint)).await?;
+    if account.owner != spl_token_2022::id() {
+        return Err(anyhow::Error::msg(format!(
+            "mint {} is not a Token-2022 mint, it has no transfer fees",
+            mint
+        )));
+    }
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&account.data)?;
+    let config = state
+        .get_extension::<TransferFeeConfig>()
+        .map_err(|_| anyhow::Error::msg(format!("mint {} has no transfer fee config", mint)))?;
+    let epoch = rpc::retry(|| rpc.get_epoch_info()).await?.epoch;
+    let fee = config
+        .calculate_epoch_fee(epoch, amount)
+        .ok_or(anyhow::Error::msg("transfer fee overflows"))?;
+    let newer_epoch: u64 = config.newer_transfer_fee.epoch.into();
+    if newer_epoch > epoch {
+        caution(&format!(
+            "the transfer fee changes in epoch {}, the transfer fails if executed from then on",
+            newer_epoch
+        ))?;
+    }
+    Ok(fee)
+}
+
+/// Token transfer held by a transaction account.
+struct StoredTransfer {
+    from: AnchorPubkey,
+    to: AnchorPubkey,
+    amount: u64,
+    /// Fee expected by a Token-2022 transfer with fee.
+    fee: Option<u64>,
+}
+
+fn stored_transfer(transaction: &coral_multisig::Transaction) -> anyhow::Result<StoredTransfer> {
+    let account = |i: usize| {
+        transaction
+            .accounts
+            .get(i)
+            .map(|acc| acc.pubkey)
+            .ok_or(anyhow::Error::msg(
+                "transaction instruction has too few accounts for a transfer",
+            ))
+    };
+    let (transfer, expected_len) = if transaction.program_id == spl_token_2022::id() {
+        let ix = Token2022Instruction::unpack(&transaction.data)?;
+        match &ix {
+            Token2022Instruction::TransferFeeExtension(
+                TransferFeeInstruction::TransferCheckedWithFee { amount, fee, .. },
+            ) => (
+                StoredTransfer {
+                    from: account(0)?,
+                    to: account(2)?,
+                    amount: *amount,
+                    fee: Some(*fee),
+                },
+                ix.pack().len(),
+            ),
+            Token2022Instruction::TransferChecked { amount, .. } => (
+                StoredTransfer {
+                    from: account(0)?,
+                    to: account(2)?,
+                    amount: *amount,
+                    fee: None,
+                },
+                ix.pack().len(),
+            ),
+            _ => {
+                return Err(anyhow::Error::msg(
+                    "transaction instruction is not transfer",
+                ))
+            }
+        }
+    } else {
+        match TokenInstruction::unpack(&transaction.data)? {
+            TokenInstruction::Transfer { amount } => (
+                StoredTransfer {
+                    from: account(0)?,
+                    to: account(1)?,
+                    amount,
+                    fee: None,
+                },
+                TokenInstruction::Transfer { amount }.pack().len(),
+            ),
+            _ => {
+                return Err(anyhow::Error::msg(
+                    "transaction instruction is not transfer",
+                ))
+            }
+        }
+    };
+    if transaction.data.len() != expected_len {
+        return Err(anyhow::Error::msg(format!(
+            "the stored instruction data is {} bytes, but a transfer takes {}",
+            transaction.data.len(),
+            expected_len
+        )));
+    }
+    Ok(transfer)
+}
+
+/// Checks that the stored account metas are the ones the stored transfer
+/// instruction expects, in order, with the multisig signer as its authority.
+/// Execution passes them on as is, so any difference fails it on-chain.
+fn check_transfer_accounts(
+    transaction: &coral_multisig::Transaction,
+    multisig_signer: &AnchorPubkey,
+) -> anyhow::Result<()> {
+    let stored: Vec<AccountMeta> = transaction.accounts.iter().map(Into::into).collect();
+    let account = |i: usize| {
+        stored
+            .get(i)
+            .map_or(AnchorPubkey::default(), |acc| acc.pubkey)
+    };
+    let expected = if transaction.program_id == spl_token_2022::id() {
+        match Token2022Instruction::unpack(&transaction.data)? {
+            Token2022Instruction::TransferFeeExtension(
+                TransferFeeInstruction::TransferCheckedWithFee {
+                    amount,
+                    decimals,
+                    fee,
+                },
+            ) => {
+                transfer_fee_instruction::transfer_checked_with_fee(
+                    &transaction.program_id,
+                    &account(0),
+                    &account(1),
+                    &account(2),
+                    multisig_signer,
+                    &[],
+                    amount,
+                    decimals,
+                    fee,
+                )?
+                .accounts
+            }
+            Token2022Instruction::TransferChecked { amount, decimals } => {
+                spl_token_2022::instruction::transfer_checked(
+                    &transaction.program_id,
+                    &account(0),
+                    &account(1),
+                    &account(2),
+                    multisig_signer,
+                    &[],
+                    amount,
+                    decimals,
+                )?
+                .accounts
+            }
+            _ => {
+                return Err(anyhow::Error::msg(
+                    "transaction instruction is not transfer",
+                ))
+            }
+        }
+    } else {
+        match TokenInstruction::unpack(&transaction.data)? {
+            TokenInstruction::Transfer { amount } => token_instruction::transfer(
+                &spl_token::id(),
+                &account(0).to_bytes().into(),
+                &account(1).to_bytes().into(),
+                &multisig_signer.to_bytes().into(),
+                &[],
+                amount,
+            )?
+            .accounts
+            .into_iter()
+            .map(|acc| AccountMeta {
+                pubkey: acc.pubkey.to_bytes().into(),
+                is_signer: acc.is_signer,
+                is_writable: acc.is_writable,
+            })
+            .collect(),
+            _ => {
+                return Err(anyhow::Error::msg(
+                    "transaction instruction is not transfer",
+                ))
+            }
+        }
+    };
+    if stored.len() != expected.len() {
+        return Err(anyhow::Error::msg(format!(
+            "the transaction stores {} accounts, but its transfer takes {}",
+            stored.len(),
+            expected.len()
+        )));
+    }
+    for (i, (stored, expected)) in stored.iter().zip(&expected).enumerate() {
+        if stored.pubkey != expected.pubkey {
+            return Err(anyhow::Error::msg(format!(
+                "stored account #{} is {}, but the transfer expects {}",
+                i, stored.pubkey, expected.pubkey
+            )));
+        }
+        if stored.is_writable != expected.is_writable || stored.is_signer != expected.is_signer {
+            return Err(anyhow::Error::msg(format!(
+                "stored account #{} {} is {}writable and {}a signer, but the transfer expects it {}writable and {}a signer",
+                i,
+                stored.pubkey,
+                if stored.is_writable { "" } else { "not " },
+                if stored.is_signer { "" } else { "not " },
+                if expected.is_writable { "" } else { "not " },
+                if expected.is_signer { "" } else { "not " },
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Token delegation change held by a transaction account.
+struct StoredDelegation {
+    /// Owner of the token account, which has to sign the change.
+    owner: AnchorPubkey,
+}
+
+/// Decodes an `Approve`, `ApproveChecked` or `Revoke` instruction of either
+/// token program, if the transaction holds one.
+fn stored_delegation(transaction: &coral_multisig::Transaction) -> Option<StoredDelegation> {
+    if transaction.program_id.to_bytes() != spl_token::id().to_bytes()
+        && transaction.program_id != spl_token_2022::id()
+    {
+        return None;
+    }
+    // Token-2022 keeps the instructions of the Token program, so one decoder
+    // covers both. Approve takes the source, delegate and owner,
+    // ApproveChecked the mint after the source, Revoke the source and owner.
+    let owner_index = match Token2022Instruction::unpack(&transaction.data).ok()? {
+        Token2022Instruction::Approve { .. } => 2,
+        Token2022Instruction::ApproveChecked { .. } => 3,
+        Token2022Instruction::Revoke => 1,
+        _ => return None,
+    };
+    Some(StoredDelegation {
+        owner: transaction.accounts.get(owner_index)?.pubkey,
+    })
+}
+
+/// Converts an instruction of the SDK used by anchor to the one of spl-token.
+fn to_spl_instruction(ix: Instruction) -> SplInstruction {
+    SplInstruction {
+        program_id: ix.program_id.to_bytes().into(),
+        accounts: ix
+            .accounts
+            .into_iter()
+            .map(|acc| SplAccountMeta {
+                pubkey: acc.pubkey.to_bytes().into(),
+                is_signer: acc.is_signer,
+                is_writable: acc.is_writable,
+            })
+            .collect(),
+        data: ix.data,
+    }
+}
+
+fn to_anchor_instruction(ix: SplInstruction) -> Instruction {
+    Instruction {
+        program_id: ix.program_id.to_bytes().into(),
+        accounts: ix
+            .accounts
+            .into_iter()
+            .map(|acc| AccountMeta {
+                pubkey: acc.pubkey.to_bytes().into(),
+                is_signer: acc.is_signer,
+                is_writable: acc.is_writable,
+            })
+            .collect(),
+        data: ix.data,
+    }
+}
+
+/// Instructions a proposal runs first, with the rent they charge their payers.
+#[derive(Clone, Default)]
+struct Setup {
+    instructions: Vec<Instruction>,
+    rents: Vec<(AnchorPubkey, u64)>,
+}
+
+/// Wraps `instruction` into a new multisig transaction account proposed by
+/// `signer`, after the `setup` instructions.
+async fn propose_transaction(
+    program: &Program<&Keypair>,
+    offline: bool,
+    output: &Output,
+    signer: &SignerArg,
+    multisig: AnchorPubkey,
+    instruction: SplInstruction,
+    setup: Setup,
+) -> anyhow::Result<AnchorPubkey> {
+    let keypair = Keypair::new();
+    let tx = proposal_message(
+        program,
+        offline,
+        signer,
+        multisig,
+        instruction,
+        keypair.pubkey(),
+        setup.instructions,
+    )
+    .await?;
+    let rent = rent_exemption(program, offline, TRANSACTION_ACCOUNT_SIZE).await?;
+    let mut rents = setup.rents;
+    rents.push((signer.rent_payer(), rent));
+    check_funds(program, offline, &tx, signer, &rents).await?;
+    output.print(&tx, &[&keypair])?;
+    let sig = keypair.sign_message(&tx.serialize());
+
+    if signer.rent_payer() != signer.signer {
+        println!(
+            "Rent payer, signing along with the signer: {}",
+            style(signer.rent_payer()).green()
+        );
+    }
+    println!(
+        "Transaction signed by transaction account: {}",
+        style(sig).green()
+    );
+    println!(
+        "Pending transaction account: {}",
+        style(keypair.pubkey()).green()
+    );
+    Ok(keypair.pubkey())
+}
+
+/// Builds the message creating the multisig account `multisig`, to be signed
+/// by `signer` and the account itself.
+async fn create_multisig_message(
+    program: &Program<&Keypair>,
+    offline: bool,
+    signer: &SignerArg,
+    owners: Vec<AnchorPubkey>,
+    threshold: u64,
+    multisig: AnchorPubkey,
+) -> anyhow::Result<Message> {
+    let accounts = multisig_accounts::CreateMultisig { multisig };
+    let (_, nonce) = derive_multisig_signer(&multisig, &program.id());
+    let instructions = multisig_instructions::CreateMultisig {
+        owners,
+        threshold,
+        nonce,
+    };
+    let req = program
+        .request()
+        .accounts(accounts)
+        .accounts(AccountMeta::new_readonly(sysvar::rent::id(), false))
+        .args(instructions)
+        .instruction(system_instruction::create_account(
+            &signer.rent_payer(),
+            &multisig,
+            rent_exemption(program, offline, MULTISIG_ACCOUNT_SIZE).await?,
+            MULTISIG_ACCOUNT_SIZE as u64,
+            &program.id(),
+        ));
+
+    build_tx(
+        signer.signer,
+        signer.nonce()?,
+        signer.nonce_account()?,
+        req.instructions()?,
+    )
+}
+
+/// Builds the message creating the transaction account `transaction` that
+/// holds `instruction`, to be signed by `signer` and the account itself.
+/// The `setup` instructions run first, e.g. creating the accounts
+/// `instruction` needs.
+async fn proposal_message(
+    program: &Program<&Keypair>,
+    offline: bool,
+    signer: &SignerArg,
+    multisig: AnchorPubkey,
+    instruction: SplInstruction,
+    transaction: AnchorPubkey,
+    setup: Vec<Instruction>,
+) -> anyhow::Result<Message> {
+    let accounts = multisig_accounts::CreateTransaction {
+        multisig,
+        transaction,
+        proposer: signer.signer,
+    };
+    let instructions = multisig_instructions::CreateTransaction {
+        pid: instruction.program_id.to_bytes().into(),
+        accs: instruction.accounts.iter().map(Into::into).collect(),
+        data: instruction.data,
+    };
+    let req = program
+        .request()
+        .accounts(accounts)
+        .accounts(AccountMeta::new_readonly(sysvar::rent::id(), false))
+        .args(instructions)
+        .instruction(system_instruction::create_account(
+            &signer.rent_payer(),
+            &transaction,
+            rent_exemption(program, offline, TRANSACTION_ACCOUNT_SIZE).await?,
+            TRANSACTION_ACCOUNT_SIZE as u64,
+            &program.id(),
+        ));
+    let mut instructions = setup;
+    instructions.extend(req.instructions()?);
+    instructions.extend(signer.memo_instruction());
+
+    build_tx(
+        signer.signer,
+        signer.nonce()?,
+        signer.nonce_account()?,
+        instructions,
+    )
+}
+
+fn approve_message(
+    program: &Program<&Keypair>,
+    signer: &SignerArg,
+    multisig: AnchorPubkey,
+    transaction: AnchorPubkey,
+) -> anyhow::Result<Message> {
+    let accounts = multisig_accounts::Approve {
+        multisig,
+        transaction,
+        owner: signer.signer,
+    };
+    let instructions = multisig_instructions::Approve {};
+    let req = program.request().accounts(accounts).args(instructions);
+
+    build_tx(
+        signer.signer,
+        signer.nonce()?,
+        signer.nonce_account()?,
+        req.instructions()?,
+    )
+}
+
+/// Refuses a stored instruction invoking a program outside `allowed`, unless
+/// no allowlist is given.
+fn check_allowed_program(
+    transaction_account: &coral_multisig::Transaction,
+    allowed: &[AnchorPubkey],
+) -> anyhow::Result<()> {
+    if allowed.is_empty() || allowed.contains(&transaction_account.program_id) {
+        return Ok(());
+    }
+    Err(SerumError::Validation(format!(
+        "the transaction invokes program {}, which isn't in --allowed-programs",
+        transaction_account.program_id
+    ))
+    .into())
+}
+
+/// Builds the message executing `transaction`, passing the accounts of the
+/// stored instruction along so that the program can invoke it.
+fn execute_message(
+    program: &Program<&Keypair>,
+    signer: &SignerArg,
+    multisig: AnchorPubkey,
+    transaction: AnchorPubkey,
+    transaction_account: &coral_multisig::Transaction,
+    compute_budget: &ComputeBudgetArgs,
+) -> anyhow::Result<Message> {
+    build_tx(
+        signer.signer,
+        signer.nonce()?,
+        signer.nonce_account()?,
+        execute_instructions(
+            program,
+            signer,
+            multisig,
+            transaction,
+            transaction_account,
+            compute_budget,
+        )?,
+    )
+}
+
+/// Instructions of the message `execute_message` builds.
+fn execute_instructions(
+    program: &Program<&Keypair>,
+    signer: &SignerArg,
+    multisig: AnchorPubkey,
+    transaction: AnchorPubkey,
+    transaction_account: &coral_multisig::Transaction,
+    compute_budget: &ComputeBudgetArgs,
+) -> anyhow::Result<Vec<Instruction>> {
+    let (multisig_pda, _) = derive_multisig_signer(&multisig, &program.id());
+    let mut remaining_accounts: Vec<AccountMeta> = transaction_account
+        .accounts
+        .iter()
+        .map(Into::into)
+        .collect();
+    // The multisig signer can only sign through the program.
+    for acc in remaining_accounts.iter_mut() {
+        acc.is_signer = false;
+    }
+
+    let accounts = multisig_accounts::ExecuteTransaction {
+        multisig,
+        multisig_signer: multisig_pda,
+        transaction,
+    };
+    let instructions = multisig_instructions::ExecuteTransaction {};
+    let req = program
+        .request()
+        .accounts(accounts)
+        .accounts(remaining_accounts)
+        .accounts(AccountMeta::new_readonly(
+            transaction_account.program_id,
+            false,
+        ))
+        .args(instructions);
+    let mut instructions = compute_budget.instructions();
+    instructions.extend(req.instructions()?);
+    instructions.extend(signer.memo_instruction());
+    Ok(instructions)
+}
+
+/// Simulates an unsigned message, returning the balances of the `watched`
+/// token accounts after it, or `None` if the simulation failed.
+async fn simulate_token_balances(
+    program: &Program<&Keypair>,
+    message: &Message,
+    watched: &[AnchorPubkey],
+) -> anyhow::Result<Option<Vec<Option<u64>>>> {
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        accounts: Some(RpcSimulateTransactionAccountsConfig {
+            encoding: None,
+            addresses: watched.iter().map(ToString::to_string).collect(),
+        }),
+        ..Default::default()
+    };
+    log::info!("simulating transaction");
+    let result = program
+        .async_rpc()
+        .simulate_transaction_with_config(&Transaction::new_unsigned(message.clone()), config)
+        .await?
+        .value;
+    if let Some(err) = result.err {
+        println!("{}", style("Simulation logs:").bold());
+        for line in result.logs.unwrap_or_default() {
+            println!("  {}", line);
+        }
+        caution(&format!("the simulation failed: {}", err))?;
+        return Ok(None);
+    }
+    Ok(Some(
+        result
+            .accounts
+            .unwrap_or_default()
+            .into_iter()
+            .map(|account| {
+                let data = account?.data.decode()?;
+                // Token-2022 accounts carry extensions after the base state.
+                let state =
+                    StateWithExtensions::<spl_token_2022::state::Account>::unpack(&data).ok()?;
+                Some(state.base.amount)
+            })
+            .collect(),
+    ))
+}
+
+/// Checks that as many signatures are given as the message header requires.
+/// Signatures are matched to the signers by position, so the missing ones
+/// are the last slots.
+fn check_signature_count(message: &Message, given: usize) -> anyhow::Result<()> {
+    sanitize_message(message)?;
+    let required = message.header.num_required_signatures as usize;
+    if given > required {
+        return Err(SerumError::Validation(format!(
+            "the message requires {} signatures, but {} were given",
+            required, given
+        ))
+        .into());
+    }
+    if given < required {
+        let missing = message.account_keys[given..required]
+            .iter()
+            .enumerate()
+            .map(|(i, key)| format!("  #{} {}", given + i, key))
+            .collect::<Vec<_>>()
+            .join("\n");
+        return Err(SerumError::Validation(format!(
+            "the message requires {} signatures, but {} were given; unfilled signer slots:\n{}",
+            required, given, missing
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Checks every signature against its signer, naming the signers whose
+/// signatures don't match the message.
+fn verify_signatures(tx: &Transaction) -> anyhow::Result<()> {
+    let invalid: Vec<_> = tx
+        .verify_with_results()
+        .into_iter()
+        .zip(&tx.message.account_keys)
+        .enumerate()
+        .filter(|(_, (valid, _))| !valid)
+        .map(|(i, (_, signer))| format!("#{} by {}", i, signer))
+        .collect();
+    if !invalid.is_empty() {
+        return Err(SerumError::Validation(format!(
+            "invalid signatures of the message: {}; pass --skip-verify to send anyway",
+            invalid.join(", ")
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Signature collected as a file of `Submit --signature-dir`.
+#[derive(Deserialize)]
+struct SignatureFile {
+    signer: String,
+    signature: String,
+}
+
+/// Assembles the signatures of `message` from the JSON signature files in
+/// `dir`, listing which required signers they cover.
+fn read_signature_dir(dir: &std::path::Path, message: &Message) -> anyhow::Result<Vec<Signature>> {
+    let required = &message.account_keys[..message.header.num_required_signatures as usize];
+    let data = message.serialize();
+    let mut signatures: Vec<Option<Signature>> = vec![None; required.len()];
+    let mut paths = std::fs::read_dir(dir)
+        .map_err(|e| anyhow::Error::msg(format!("failed to read {}: {}", dir.display(), e)))?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    paths.sort();
+    for path in paths {
+        if path.extension().is_none_or(|extension| extension != "json") {
+            log::debug!("skipping {}", path.display());
+            continue;
+        }
+        let invalid = |what: String| -> anyhow::Error {
+            SerumError::Validation(format!("{}: {}", path.display(), what)).into()
+        };
+        let file: SignatureFile = serde_json::from_str(&std::fs::read_to_string(&path)?)
+            .map_err(|e| invalid(e.to_string()))?;
+        let signer: AnchorPubkey = file
+            .signer
+            .parse()
+            .map_err(|_| invalid(format!("malformed signer {}", file.signer)))?;
+        let signature: Signature = file
+            .signature
+            .parse()
+            .map_err(|_| invalid(format!("malformed signature {}", file.signature)))?;
+        let slot = required
+            .iter()
+            .position(|key| *key == signer)
+            .ok_or_else(|| invalid(format!("{} is not a signer of the message", signer)))?;
+        if !signature.verify(signer.as_ref(), &data) {
+            return Err(invalid(format!(
+                "the signature of {} doesn't verify",
+                signer
+            )));
+        }
+        signatures[slot] = Some(signature);
+    }
+
+    println!("Signers:");
+    for (key, signature) in required.iter().zip(&signatures) {
+        println!(
+            "  {} {}",
+            key,
+            if signature.is_some() {
+                style("covered").green()
+            } else {
+                style("missing").yellow()
+            }
+        );
+    }
+    let missing: Vec<_> = required
+        .iter()
+        .zip(&signatures)
+        .filter(|(_, signature)| signature.is_none())
+        .map(|(key, _)| key.to_string())
+        .collect();
+    if !missing.is_empty() {
+        return Err(SerumError::Validation(format!(
+            "{} has no signatures of {}",
+            dir.display(),
+            missing.join(", ")
+        ))
+        .into());
+    }
+    Ok(signatures.into_iter().flatten().collect())
+}
+
+/// A base64 message with its signatures, in the order of the required signers.
+#[derive(Serialize, Deserialize)]
+struct SignedTransaction {
+    transaction: String,
+    signatures: Vec<String>,
+    /// Required signers, listed to show whose signatures are still missing.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    signers: Vec<String>,
+}
+
+impl SignedTransaction {
+    fn unsigned(message: &Message) -> Self {
+        Self {
+            transaction: BASE64_STANDARD.encode(message.serialize()),
+            signatures: Vec::new(),
+            signers: message.account_keys[..message.header.num_required_signatures as usize]
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+        }
+    }
+
+    fn to_transaction(&self) -> anyhow::Result<Transaction> {
+        let message: Message = bincode::deserialize(&BASE64_STANDARD.decode(&self.transaction)?)?;
+        let signatures = self
+            .signatures
+            .iter()
+            .map(|sig| sig.parse())
+            .collect::<Result<Vec<Signature>, _>>()?;
+        check_signature_count(&message, signatures.len())?;
+        Ok(Transaction {
+            signatures,
+            message,
+        })
+    }
+}
+
+/// Proposer of a transaction account, as the `create_transaction`
+/// instruction of its oldest transaction names it. `None` once the RPC node
+/// no longer has that transaction.
+async fn recorded_proposer(
+    program: &Program<&Keypair>,
+    transaction: &AnchorPubkey,
+) -> anyhow::Result<Option<AnchorPubkey>> {
+    let rpc = program.async_rpc();
+    log::info!("fetching the signatures of {}", transaction);
+    let signatures = rpc::retry(|| rpc.get_signatures_for_address(transaction)).await?;
+    let Some(oldest) = signatures.last() else {
+        return Ok(None);
+    };
+    let signature: Signature = oldest.signature.parse()?;
+    log::info!("fetching the creation of {}: {}", transaction, signature);
+    let config = confirmed_transaction_config(&rpc);
+    let confirmed = rpc::retry(|| rpc.get_transaction_with_config(&signature, config)).await?;
+    let Some(tx) = confirmed.transaction.transaction.decode() else {
+        return Ok(None);
+    };
+    let keys = tx.message.static_account_keys();
+    let key = |index: u8| keys.get(index as usize).copied();
+    // CreateTransaction takes the multisig, the transaction and the proposer.
+    Ok(tx.message.instructions().iter().find_map(|ix| {
+        let creates = key(ix.program_id_index) == Some(program.id())
+            && decode::multisig_instruction_name(&ix.data) == Some("create_transaction")
+            && ix.accounts.get(1).and_then(|&i| key(i)) == Some(*transaction);
+        creates.then(|| ix.accounts.get(2).and_then(|&i| key(i)))?
+    }))
+}
+
+/// Checks that `proposer` created the transaction account, as recorded on
+/// the cluster.
+async fn check_proposer(
+    program: &Program<&Keypair>,
+    transaction: &AnchorPubkey,
+    proposer: &AnchorPubkey,
+) -> anyhow::Result<()> {
+    match recorded_proposer(program, transaction).await? {
+        Some(recorded) if recorded == *proposer => Ok(()),
+        Some(recorded) => Err(SerumError::Validation(format!(
+            "transaction {} was proposed by {}, not by {}",
+            transaction, recorded, proposer
+        ))
+        .into()),
+        None => caution(&format!(
+            "the creation of transaction {} is no longer available from the RPC node, its proposer can't be checked",
+            transaction
+        )),
+    }
+}
+
+/// Fetches confirmed transactions at the commitment of the command, or at
+/// confirmed, the lowest they can be fetched at.
+fn confirmed_transaction_config(rpc: &RpcClient) -> RpcTransactionConfig {
+    RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        commitment: Some(if rpc.commitment().is_at_least_confirmed() {
+            rpc.commitment()
+        } else {
+            CommitmentConfig::confirmed()
+        }),
+        max_supported_transaction_version: Some(0),
+    }
+}
+
+/// Refuses a transaction proposed under a previous owner set, which the
+/// program neither lets owners approve nor executes.
+fn check_owner_set(
+    multisig: &coral_multisig::Multisig,
+    transaction: &coral_multisig::Transaction,
+) -> anyhow::Result<()> {
+    if multisig.owner_set_seqno != transaction.owner_set_seqno {
+        return Err(SerumError::Validation(
+            "the owner set changed since the proposal, the transaction can't be approved or executed, propose it again"
+                .to_string(),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// How many owners approved a transaction, out of the threshold required to execute it.
+#[derive(Serialize)]
+struct ApprovalCount {
+    approved: u64,
+    threshold: u64,
+}
+
+impl ApprovalCount {
+    fn new(multisig: &coral_multisig::Multisig, transaction: &coral_multisig::Transaction) -> Self {
+        Self {
+            approved: transaction.signers.iter().filter(|&&signed| signed).count() as u64,
+            threshold: multisig.threshold,
+        }
+    }
+
+    fn is_met(&self) -> bool {
+        self.approved >= self.threshold
+    }
+}
+
+async fn fetch_approval_count(
+    program: &Program<&Keypair>,
+    transaction: AnchorPubkey,
+) -> anyhow::Result<ApprovalCount> {
+    log::info!("fetching transaction account {}", transaction);
+    let transaction: coral_multisig::Transaction =
+        rpc::retry(|| program.account(transaction)).await?;
+    let multisig = load_multisig(program, transaction.multisig).await?;
+    Ok(ApprovalCount::new(&multisig, &transaction))
+}
+
+/// Loads a transaction account, explaining a failure to decode it by an
+/// account created too small for the instruction stored in it.
+async fn fetch_checked_transaction(
+    program: &Program<&Keypair>,
+    transaction: AnchorPubkey,
+) -> anyhow::Result<coral_multisig::Transaction> {
+    log::info!("fetching transaction account {}", transaction);
+    let rpc = program.async_rpc();
+    let data = rpc::retry(|| rpc.get_account_data(&transaction)).await?;
+    let account = coral_multisig::Transaction::try_deserialize(&mut data.as_slice()).map_err(|e| {
+        anyhow::Error::msg(format!(
+            "the transaction account is {} bytes and can't be decoded, it was likely created too small for the instruction: {}",
+            data.len(),
+            e
+        ))
+    })?;
+    Ok(account)
+}
+
+/// Slot the account was created in, as the slot of its oldest signature, or
+/// `None` once the RPC node pruned it from its history. Transaction accounts
+/// are only touched by their creation, the approvals and the execution, well
+/// within a single page of signatures.
+async fn creation_slot(
+    program: &Program<&Keypair>,
+    address: &AnchorPubkey,
+) -> anyhow::Result<Option<u64>> {
+    let rpc = program.async_rpc();
+    let signatures = rpc::retry(|| rpc.get_signatures_for_address(address)).await?;
+    Ok(signatures.last().map(|sig| sig.slot))
+}
+
+/// Loads a multisig account, checking that its signer was derived with the given program id.
+async fn load_multisig(
+    program: &Program<&Keypair>,
+    multisig: AnchorPubkey,
+) -> anyhow::Result<coral_multisig::Multisig> {
+    log::info!("fetching multisig account {}", multisig);
+    let account: coral_multisig::Multisig = rpc::retry(|| program.account(multisig)).await?;
+    let (_, bump) = derive_multisig_signer(&multisig, &program.id());
+    if bump != account.nonce {
+        return Err(anyhow::Error::msg(format!(
+            "multisig signer bump {} doesn't match the stored nonce {}, --pid or --multisig is likely wrong",
+            bump, account.nonce
+        )));
+    }
+    Ok(account)
 }
 
 fn derive_multisig_signer(
     multisig: &AnchorPubkey,
     program_id: &AnchorPubkey,
 ) -> (AnchorPubkey, u8) {
-    AnchorPubkey::find_program_address(&[multisig.as_ref()], program_id)
+    let (pda, bump) = AnchorPubkey::find_program_address(&[multisig.as_ref()], program_id);
+    log::debug!("multisig {} signer PDA: {} (bump {})", multisig, pda, bump);
+    (pda, bump)
 }
@@ -0,0 +1,236 @@
+//! Signing request manifests, handing a message over to the signers together
+//! with what they need to review it. `solana-signer --request` reads them,
+//! and `RebuildMessage` rebuilds the message from their instructions to check
+//! the distributed one.
+
+use std::io::Write;
+use std::path::Path;
+
+use anchor_client::{
+    anchor_lang::solana_program::hash,
+    solana_sdk::{
+        instruction::{AccountMeta, Instruction},
+        message::Message,
+        pubkey::Pubkey,
+        sanitize::Sanitize,
+    },
+};
+use base64::{engine::Engine, prelude::BASE64_STANDARD};
+use serde::{Deserialize, Serialize};
+
+use crate::error::SerumError;
+use crate::{durable_nonce_account, durable_nonce_authority, required_signers};
+
+/// Version 2 lists the nonce, the recent blockhash and the instructions, and
+/// no longer a summary of them.
+pub const VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+pub struct SigningRequest {
+    version: u32,
+    /// Base64 message to sign.
+    message: String,
+    /// Base58 SHA-256 of the serialized message.
+    message_hash: String,
+    /// Required signers, in the order of their signatures.
+    signers: Vec<RequiredSigner>,
+    nonce: Option<NonceRequirement>,
+    /// Base58 recent blockhash, or nonce value.
+    recent_blockhash: String,
+    /// Instructions of the message, in order.
+    instructions: Vec<RequestInstruction>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RequiredSigner {
+    pubkey: String,
+    roles: Vec<String>,
+}
+
+/// Durable nonce the message is only valid with.
+#[derive(Serialize, Deserialize)]
+struct NonceRequirement {
+    account: String,
+    authority: String,
+    value: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RequestInstruction {
+    program_id: String,
+    /// Accounts, as `PUBKEY`, `PUBKEY:s`, `PUBKEY:w` or `PUBKEY:sw`.
+    accounts: Vec<String>,
+    /// Base64 instruction data.
+    data: String,
+}
+
+fn invalid(what: &str) -> anyhow::Error {
+    SerumError::Validation(format!("invalid signing request: {}", what)).into()
+}
+
+/// Labels the account at `index` of the message with its flags.
+fn account_label(message: &Message, index: u8) -> String {
+    let index = index as usize;
+    let header = &message.header;
+    let num_signers = header.num_required_signatures as usize;
+    let (is_signer, is_writable) = if index < num_signers {
+        (
+            true,
+            index < num_signers - header.num_readonly_signed_accounts as usize,
+        )
+    } else {
+        (
+            false,
+            index < message.account_keys.len() - header.num_readonly_unsigned_accounts as usize,
+        )
+    };
+    let flags = match (is_signer, is_writable) {
+        (false, false) => "",
+        (true, false) => ":s",
+        (false, true) => ":w",
+        (true, true) => ":sw",
+    };
+    format!("{}{}", message.account_keys[index], flags)
+}
+
+fn parse_account_label(label: &str) -> anyhow::Result<AccountMeta> {
+    let (pubkey, flags) = label.split_once(':').unwrap_or((label, ""));
+    let (is_signer, is_writable) = match flags {
+        "" => (false, false),
+        "s" => (true, false),
+        "w" => (false, true),
+        "sw" => (true, true),
+        _ => return Err(invalid(&format!("malformed account {:?}", label))),
+    };
+    let pubkey = pubkey
+        .parse()
+        .map_err(|_| invalid(&format!("malformed account {:?}", label)))?;
+    Ok(AccountMeta {
+        pubkey,
+        is_signer,
+        is_writable,
+    })
+}
+
+impl SigningRequest {
+    pub fn new(message: &Message) -> anyhow::Result<Self> {
+        message
+            .sanitize()
+            .map_err(|e| invalid(&format!("malformed message: {}", e)))?;
+        let nonce = durable_nonce_account(message).map(|account| NonceRequirement {
+            account: account.to_string(),
+            authority: durable_nonce_authority(message).map_or(String::new(), |a| a.to_string()),
+            value: message.recent_blockhash.to_string(),
+        });
+        let signers = required_signers(message)
+            .into_iter()
+            .map(|(key, roles)| RequiredSigner {
+                pubkey: key.to_string(),
+                roles: roles.into_iter().map(str::to_string).collect(),
+            })
+            .collect();
+        let instructions = message
+            .instructions
+            .iter()
+            .map(|ix| RequestInstruction {
+                program_id: message.account_keys[ix.program_id_index as usize].to_string(),
+                accounts: ix
+                    .accounts
+                    .iter()
+                    .map(|&index| account_label(message, index))
+                    .collect(),
+                data: BASE64_STANDARD.encode(&ix.data),
+            })
+            .collect();
+        let serialized = message.serialize();
+        Ok(Self {
+            version: VERSION,
+            message: BASE64_STANDARD.encode(&serialized),
+            message_hash: hash::hash(&serialized).to_string(),
+            signers,
+            nonce,
+            recent_blockhash: message.recent_blockhash.to_string(),
+            instructions,
+        })
+    }
+
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            anyhow::Error::msg(format!("failed to read request {}: {}", path.display(), e))
+        })?;
+        let request: Self = serde_json::from_str(&content)?;
+        if request.version != VERSION {
+            return Err(invalid(&format!("unsupported version {}", request.version)));
+        }
+        Ok(request)
+    }
+
+    /// Distributed message, as signed.
+    pub fn message(&self) -> anyhow::Result<Message> {
+        let data = BASE64_STANDARD
+            .decode(&self.message)
+            .map_err(|_| invalid("the message is not base64"))?;
+        let message: Message =
+            bincode::deserialize(&data).map_err(|_| invalid("the message can't be decoded"))?;
+        message
+            .sanitize()
+            .map_err(|e| invalid(&format!("malformed message: {}", e)))?;
+        Ok(message)
+    }
+
+    /// Compiles the listed instructions into a message paid by the first
+    /// signer, independently of the distributed one.
+    pub fn rebuild(&self) -> anyhow::Result<Message> {
+        let instructions = self
+            .instructions
+            .iter()
+            .map(|ix| {
+                Ok(Instruction {
+                    program_id: ix
+                        .program_id
+                        .parse()
+                        .map_err(|_| invalid("malformed program id"))?,
+                    accounts: ix
+                        .accounts
+                        .iter()
+                        .map(|label| parse_account_label(label))
+                        .collect::<anyhow::Result<_>>()?,
+                    data: BASE64_STANDARD
+                        .decode(&ix.data)
+                        .map_err(|_| invalid("instruction data is not base64"))?,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let payer: Pubkey = self
+            .signers
+            .first()
+            .ok_or_else(|| invalid("no signers are listed"))?
+            .pubkey
+            .parse()
+            .map_err(|_| invalid("malformed fee payer"))?;
+        let mut message = Message::new(&instructions, Some(&payer));
+        message.recent_blockhash = self
+            .recent_blockhash
+            .parse()
+            .map_err(|_| invalid("malformed recent blockhash"))?;
+        Ok(message)
+    }
+
+    /// Writes the request to a new file, refusing to overwrite an existing one.
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|e| {
+                anyhow::Error::msg(format!("failed to create {}: {}", path.display(), e))
+            })?;
+        file.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    /// File name identifying the request by its message hash.
+    pub fn file_name(&self) -> String {
+        format!("signing-request-{}.json", &self.message_hash[..8])
+    }
+}
@@ -0,0 +1,123 @@
+//! Signing plans walking the owners through the whole ceremony of a multisig
+//! operation, before any message is built.
+
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use clap::ValueEnum;
+use crossterm::style::Stylize;
+
+use crate::color::style;
+use crate::decode;
+
+/// Operation proposed to the multisig.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Action {
+    TokenTransfer,
+    SolTransfer,
+    Drain,
+    ApproveDelegate,
+    Revoke,
+    Raw,
+}
+
+impl Action {
+    fn description(self) -> &'static str {
+        match self {
+            Action::TokenTransfer => "token transfer",
+            Action::SolTransfer => "SOL transfer",
+            Action::Drain => "token drain",
+            Action::ApproveDelegate => "token delegation",
+            Action::Revoke => "token delegation revocation",
+            Action::Raw => "arbitrary instruction",
+        }
+    }
+
+    /// Command building the proposal.
+    fn propose_command(self) -> &'static str {
+        match self {
+            Action::TokenTransfer => "CreateTokenTransferTransaction",
+            Action::SolTransfer => "CreateSolTransferTransaction",
+            Action::Drain => "CreateDrainTransaction",
+            Action::ApproveDelegate => "CreateApproveDelegateTransaction",
+            Action::Revoke => "CreateRevokeTransaction",
+            Action::Raw => "CreateRawTransaction",
+        }
+    }
+
+    /// Command building the execution once the proposal is approved.
+    fn execute_command(self) -> &'static str {
+        match self {
+            Action::TokenTransfer | Action::Drain | Action::ApproveDelegate | Action::Revoke => {
+                "ExecuteTokenTransferTransaction"
+            }
+            Action::SolTransfer | Action::Raw => "ApproveAndExecute",
+        }
+    }
+}
+
+/// Multisig the plan is made for.
+pub struct Multisig<'a> {
+    pub address: Pubkey,
+    pub owners: &'a [Pubkey],
+    pub threshold: u64,
+}
+
+fn step(number: usize, title: &str) {
+    println!("\n{}", style(format!("{}. {}", number, title)).bold());
+}
+
+/// Prints the steps of `action` from the proposal to the execution.
+/// `transaction_rent` is the rent of the transaction account the proposal
+/// creates.
+pub fn print(action: Action, multisig: &Multisig, proposer: Option<Pubkey>, transaction_rent: u64) {
+    println!(
+        "Signing plan of a {} from multisig {}, approved by {} of {} owners",
+        action.description(),
+        style(multisig.address).green(),
+        style(multisig.threshold).green(),
+        style(multisig.owners.len()).green()
+    );
+
+    step(1, &format!("Propose with {}", action.propose_command()));
+    match proposer {
+        Some(proposer) => println!("Proposer: {}", style(proposer).green()),
+        None => println!("Proposer: any owner"),
+    }
+    println!("Creating the proposal counts as the proposer's approval.");
+    println!(
+        "Signers: the proposer, paying the fee and {} of rent for the transaction account (or \
+         --rent-payer), and the keypair of the new transaction account.",
+        decode::format_lamports(transaction_rent)
+    );
+    println!("Nonce: a nonce account authorized by the proposer.");
+
+    let remaining = multisig.threshold.saturating_sub(1);
+    step(2, "Approve with Approve");
+    if remaining == 0 {
+        println!("No further approvals are needed.");
+    } else {
+        println!("Any {} of the other owners approve:", remaining);
+        for owner in multisig
+            .owners
+            .iter()
+            .filter(|owner| Some(**owner) != proposer)
+        {
+            println!("  {}", owner);
+        }
+        println!(
+            "Signers: each approver signs a message of their own, paying its fee, once the \
+             proposal has landed."
+        );
+        println!("Nonce: a nonce account authorized by each approver.");
+    }
+
+    step(3, &format!("Execute with {}", action.execute_command()));
+    println!(
+        "Signers: any key, paying the fee, once {} approvals have landed.",
+        multisig.threshold
+    );
+    println!("Nonce: a nonce account authorized by the executing key.");
+    println!(
+        "\nEvery message stays valid until its nonce advances; submit each step before the \
+         messages of the next one are built."
+    );
+}
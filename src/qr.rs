@@ -0,0 +1,164 @@
+//! Splitting of transactions into a sequence of QR codes, for messages too
+//! large for a single code, and their reassembly once scanned.
+//!
+//! Each code holds one chunk of the encoded message as
+//! `serum:<SEQUENCE>/<TOTAL>:<DATA>`, numbered from 1, and the scanned chunks
+//! are given one per line, in order.
+
+use std::io::Read;
+use std::path::Path;
+
+use crate::error::SerumError;
+
+const PREFIX: &str = "serum:";
+
+/// Data carried by each chunk, small enough to keep the codes easy to scan.
+const CHUNK_SIZE: usize = 400;
+
+fn invalid(message: String) -> anyhow::Error {
+    SerumError::Validation(message).into()
+}
+
+/// Splits an encoded message or transaction into the contents of its QR
+/// codes, in order.
+pub fn chunks(encoded: &str) -> Vec<String> {
+    // The encodings are ASCII, so byte chunks are character chunks.
+    let data: Vec<&str> = encoded
+        .as_bytes()
+        .chunks(CHUNK_SIZE)
+        .map(|chunk| std::str::from_utf8(chunk).expect("encoded data is ASCII"))
+        .collect();
+    data.iter()
+        .enumerate()
+        .map(|(i, chunk)| format!("{}{}/{}:{}", PREFIX, i + 1, data.len(), chunk))
+        .collect()
+}
+
+/// Parses a chunk into its sequence number, the total number of chunks and
+/// its data.
+fn parse_chunk(chunk: &str) -> anyhow::Result<(usize, usize, &str)> {
+    let malformed = || invalid(format!("malformed QR chunk {:?}", chunk));
+    let rest = chunk.strip_prefix(PREFIX).ok_or_else(malformed)?;
+    let (header, data) = rest.split_once(':').ok_or_else(malformed)?;
+    let (sequence, total) = header.split_once('/').ok_or_else(malformed)?;
+    let (sequence, total) = (
+        sequence.parse().map_err(|_| malformed())?,
+        total.parse().map_err(|_| malformed())?,
+    );
+    if sequence == 0 || sequence > total {
+        return Err(malformed());
+    }
+    Ok((sequence, total, data))
+}
+
+/// Joins the data of the scanned chunks, one per line, rejecting missing,
+/// duplicated or out of order ones.
+pub fn reassemble(scanned: &str) -> anyhow::Result<String> {
+    let chunks = scanned
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(parse_chunk)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let total = match chunks.first() {
+        Some(&(_, total, _)) => total,
+        None => return Err(invalid("no QR chunks were given".to_string())),
+    };
+    let mut data = String::new();
+    for (i, &(sequence, chunk_total, chunk)) in chunks.iter().enumerate() {
+        if chunk_total != total {
+            return Err(invalid(format!(
+                "QR chunk {} is one of {}, but the first chunk is one of {}",
+                sequence, chunk_total, total
+            )));
+        }
+        if sequence != i + 1 {
+            return Err(invalid(format!(
+                "expected QR chunk {} of {}, got chunk {}",
+                i + 1,
+                total,
+                sequence
+            )));
+        }
+        data.push_str(chunk);
+    }
+    if chunks.len() != total {
+        return Err(invalid(format!(
+            "got {} of {} QR chunks, chunks {} to {} are missing",
+            chunks.len(),
+            total,
+            chunks.len() + 1,
+            total
+        )));
+    }
+    Ok(data)
+}
+
+/// Reads the scanned chunks from a file, or from stdin for `-`, and
+/// reassembles them.
+pub fn read(path: &Path) -> anyhow::Result<String> {
+    let mut scanned = String::new();
+    if path == Path::new("-") {
+        std::io::stdin().read_to_string(&mut scanned)?;
+    } else {
+        scanned = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::Error::msg(format!("failed to read {}: {}", path.display(), e)))?;
+    }
+    reassemble(&scanned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoded() -> String {
+        (0..1000)
+            .map(|i| char::from(b'A' + (i % 26) as u8))
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let encoded = encoded();
+        let chunks = chunks(&encoded);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks[0].starts_with("serum:1/3:"));
+        assert_eq!(reassemble(&chunks.join("\n")).unwrap(), encoded);
+    }
+
+    #[test]
+    fn round_trip_single_chunk() {
+        let chunks = chunks("AQID");
+        assert_eq!(chunks, ["serum:1/1:AQID"]);
+        assert_eq!(reassemble(&chunks.join("\n")).unwrap(), "AQID");
+    }
+
+    #[test]
+    fn rejects_out_of_order_chunks() {
+        let mut chunks = chunks(&encoded());
+        chunks.swap(0, 1);
+        let err = reassemble(&chunks.join("\n")).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("expected QR chunk 1 of 3, got chunk 2"));
+    }
+
+    #[test]
+    fn rejects_missing_chunks() {
+        let chunks = chunks(&encoded());
+        let err = reassemble(&chunks[..2].join("\n")).unwrap_err();
+        assert!(err.to_string().contains("chunks 3 to 3 are missing"));
+        let err = reassemble(&[&chunks[0], &chunks[2]].map(String::as_str).join("\n")).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("expected QR chunk 2 of 3, got chunk 3"));
+    }
+
+    #[test]
+    fn rejects_malformed_chunks() {
+        assert!(reassemble("").is_err());
+        assert!(reassemble("serum:0/1:AQID").is_err());
+        assert!(reassemble("serum:2/1:AQID").is_err());
+        assert!(reassemble("AQID").is_err());
+    }
+}
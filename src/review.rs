@@ -0,0 +1,197 @@
+//! Review documents for circulating a message to the approvers, with
+//! everything they need to sign it off in one place.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use anchor_client::solana_sdk::{message::Message, pubkey::Pubkey, sanitize::Sanitize};
+use clap::ValueEnum;
+use spl_token::instruction::TokenInstruction;
+
+use crate::error::SerumError;
+use crate::{decode, durable_nonce_account, message_hash, required_signers};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ReviewFormat {
+    Markdown,
+    Text,
+}
+
+struct Document {
+    format: ReviewFormat,
+    out: String,
+}
+
+impl Document {
+    fn heading(&mut self, title: &str) {
+        match self.format {
+            ReviewFormat::Markdown => writeln!(self.out, "\n## {}\n", title),
+            ReviewFormat::Text => writeln!(self.out, "\n{}\n{}", title, "-".repeat(title.len())),
+        }
+        .expect("writing to a string");
+    }
+
+    fn item(&mut self, text: &str) {
+        match self.format {
+            ReviewFormat::Markdown => writeln!(self.out, "- {}", text),
+            ReviewFormat::Text => writeln!(self.out, "  {}", text),
+        }
+        .expect("writing to a string");
+    }
+
+    fn sub_item(&mut self, text: &str) {
+        match self.format {
+            ReviewFormat::Markdown => writeln!(self.out, "  - {}", text),
+            ReviewFormat::Text => writeln!(self.out, "      {}", text),
+        }
+        .expect("writing to a string");
+    }
+
+    /// Formats an address or hash, as code in markdown.
+    fn code(&self, value: impl std::fmt::Display) -> String {
+        match self.format {
+            ReviewFormat::Markdown => format!("`{}`", value),
+            ReviewFormat::Text => value.to_string(),
+        }
+    }
+}
+
+/// Amount of a token transfer, in UI units when its decimals are known.
+fn transfer_amount(program_id: &Pubkey, data: &[u8], decimals: Option<u8>) -> Option<String> {
+    if program_id.to_bytes() != spl_token::id().to_bytes() {
+        return None;
+    }
+    match TokenInstruction::unpack(data).ok()? {
+        TokenInstruction::Transfer { amount } => Some(match decimals {
+            Some(decimals) => decode::format_amount(amount, decimals),
+            None => format!("{} base units", amount),
+        }),
+        TokenInstruction::TransferChecked { amount, decimals } => {
+            Some(decode::format_amount(amount, decimals))
+        }
+        _ => None,
+    }
+}
+
+/// Renders the review of `message`. `decimals` holds the mint decimals of
+/// the token transfers, or of the proposed ones, by instruction index, for
+/// amounts in UI units.
+pub fn render(
+    message: &Message,
+    multisig_program: &Pubkey,
+    format: ReviewFormat,
+    decimals: &HashMap<usize, u8>,
+) -> anyhow::Result<String> {
+    message
+        .sanitize()
+        .map_err(|e| SerumError::Validation(format!("malformed message: {}", e)))?;
+    let mut doc = Document {
+        format,
+        out: String::new(),
+    };
+    let title = "Transaction review";
+    match format {
+        ReviewFormat::Markdown => doc.out.push_str(&format!("# {}\n", title)),
+        ReviewFormat::Text => {
+            doc.out
+                .push_str(&format!("{}\n{}\n", title, "=".repeat(title.len())))
+        }
+    }
+
+    doc.heading("Checksum");
+    let hash = doc.code(message_hash(message));
+    doc.item(&format!("Message hash (SHA-256): {}", hash));
+
+    doc.heading("Signers");
+    for (i, (key, roles)) in required_signers(message).into_iter().enumerate() {
+        let key = doc.code(key);
+        doc.item(&format!("#{} {} ({})", i, key, roles.join(", ")));
+    }
+
+    doc.heading("Durable nonce");
+    match durable_nonce_account(message) {
+        Some(account) => {
+            let (account, value) = (doc.code(account), doc.code(message.recent_blockhash));
+            doc.item(&format!("Account: {}", account));
+            if let Some(authority) = nonce_authority {
+                let authority = doc.code(authority);
+                doc.item(&format!("Authority: {}", authority));
+            }
+            doc.item(&format!("Value: {}", value));
+        }
+        None => {
+            let blockhash = doc.code(message.recent_blockhash);
+            doc.item(&format!(
+                "None, the message uses the recent blockhash {} and expires within minutes",
+                blockhash
+            ));
+        }
+    }
+
+    doc.heading("Instructions");
+    for (i, ix) in message.instructions.iter().enumerate() {
+        let program_id = &message.account_keys[ix.program_id_index as usize];
+        doc.item(&format!(
+            "#{}: {}",
+            i,
+            decode::describe_instruction(program_id, multisig_program, &ix.data)
+        ));
+        let program = doc.code(program_id);
+        doc.sub_item(&format!("Program: {}", program));
+        if let Some(amount) = transfer_amount(program_id, &ix.data, decimals.get(&i).copied()) {
+            doc.sub_item(&format!("Amount: {}", amount));
+        }
+        let roles = decode::account_roles(program_id, &ix.data);
+        for (position, &index) in ix.accounts.iter().enumerate() {
+            let index = index as usize;
+            let mut flags = Vec::new();
+            if message.is_signer(index) {
+                flags.push("signer");
+            }
+            if message.is_writable(index) {
+                flags.push("writable");
+            }
+            let key = doc.code(message.account_keys[index]);
+            doc.sub_item(&format!(
+                "Account: {} {:?}{}",
+                key,
+                flags,
+                decode::role_label(roles, position)
+            ));
+        }
+        if program_id != multisig_program {
+            continue;
+        }
+        if let Some((proposed_program, accounts, data)) = decode::proposed_instruction(&ix.data) {
+            doc.sub_item(&format!(
+                "Proposes: {}",
+                decode::describe_instruction(&proposed_program, multisig_program, &data)
+            ));
+            let program = doc.code(proposed_program);
+            doc.sub_item(&format!("Proposed program: {}", program));
+            if let Some(amount) =
+                transfer_amount(&proposed_program, &data, decimals.get(&i).copied())
+            {
+                doc.sub_item(&format!("Proposed amount: {}", amount));
+            }
+            let roles = decode::account_roles(&proposed_program, &data);
+            for (position, account) in accounts.iter().enumerate() {
+                let mut flags = Vec::new();
+                if account.is_signer {
+                    flags.push("signer");
+                }
+                if account.is_writable {
+                    flags.push("writable");
+                }
+                let key = doc.code(account.pubkey);
+                doc.sub_item(&format!(
+                    "Proposed account: {} {:?}{}",
+                    key,
+                    flags,
+                    decode::role_label(roles, position)
+                ));
+            }
+        }
+    }
+    Ok(doc.out)
+}
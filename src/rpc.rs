@@ -0,0 +1,62 @@
+//! Retries of RPC requests rejected by rate limiting of the endpoint.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use anchor_client::solana_client::client_error::{ClientError, ClientErrorKind};
+
+use crate::warn;
+
+static RETRIES: AtomicU32 = AtomicU32::new(0);
+
+pub fn set_retries(retries: u32) {
+    RETRIES.store(retries, Ordering::Relaxed);
+}
+
+/// Whether the request failed with HTTP 429 Too Many Requests.
+fn is_rate_limited(err: &anyhow::Error) -> bool {
+    let client_error = match err.downcast_ref::<anchor_client::ClientError>() {
+        Some(anchor_client::ClientError::SolanaClientError(e)) => Some(e),
+        _ => err.downcast_ref::<ClientError>(),
+    };
+    matches!(
+        client_error.map(ClientError::kind),
+        Some(ClientErrorKind::Reqwest(e)) if e.status().is_some_and(|status| status.as_u16() == 429)
+    )
+}
+
+/// Runs `request`, backing off exponentially while the endpoint rate limits
+/// it, at most `--rpc-retries` times.
+pub async fn retry<T, E, F, Fut>(mut request: F) -> anyhow::Result<T>
+where
+    E: Into<anyhow::Error>,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let retries = RETRIES.load(Ordering::Relaxed);
+    let mut attempt = 0;
+    loop {
+        let err = match request().await {
+            Ok(value) => return Ok(value),
+            Err(err) => err.into(),
+        };
+        if !is_rate_limited(&err) {
+            return Err(err);
+        }
+        if attempt >= retries {
+            return Err(err.context(
+                "the RPC endpoint keeps rate limiting requests, consider a private one passed with --cluster <URL>",
+            ));
+        }
+        attempt += 1;
+        let delay = Duration::from_secs(1 << attempt.min(5));
+        warn(&format!(
+            "rate limited, retrying in {}s ({}/{})",
+            delay.as_secs(),
+            attempt,
+            retries
+        ));
+        tokio::time::sleep(delay).await;
+    }
+}
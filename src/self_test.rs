@@ -0,0 +1,278 @@
+//! End-to-end run of the multisig flow against a local validator that has the
+//! multisig program loaded, e.g.
+//!
+//! ```sh
+//! solana-test-validator --bpf-program <PID> coral_multisig.so
+//! serum-multisig-client --cluster localhost --pid <PID> self-test
+//! ```
+
+use anchor_client::{
+    solana_sdk::{
+        instruction::Instruction,
+        message::Message,
+        native_token::LAMPORTS_PER_SOL,
+        nonce::State as NonceState,
+        pubkey::Pubkey as AnchorPubkey,
+        signature::{Keypair, Signer},
+        system_instruction,
+        transaction::Transaction,
+    },
+    Cluster, Program,
+};
+use crossterm::style::Stylize;
+use spl_token::{instruction as token_instruction, solana_program::program_pack::Pack};
+
+use crate::color::style;
+use crate::{
+    approve_message, create_multisig_message, current_nonce, derive_multisig_signer,
+    execute_message, fetch_approval_count, fetch_token_account, proposal_message,
+    to_anchor_instruction, ComputeBudgetArgs, MintDecimals, SignerArg,
+};
+
+const AIRDROP_LAMPORTS: u64 = 10 * LAMPORTS_PER_SOL;
+const DECIMALS: u8 = 6;
+const MINTED_AMOUNT: u64 = 1_000_000_000;
+const TRANSFER_AMOUNT: u64 = 250_000_000;
+
+/// Whether the cluster is served by a validator on this machine.
+pub fn is_local(cluster: &Cluster) -> bool {
+    let url = cluster.url();
+    url.starts_with("http://127.0.0.1") || url.starts_with("http://localhost")
+}
+
+fn step(description: &str) {
+    println!("{}", style(description).bold());
+}
+
+/// Sends instructions with a recent blockhash, paid by the first signer.
+async fn send_instructions(
+    program: &Program<&Keypair>,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> anyhow::Result<()> {
+    let rpc = program.async_rpc();
+    let blockhash = rpc.get_latest_blockhash().await?;
+    let tx = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&signers[0].pubkey()),
+        signers,
+        blockhash,
+    );
+    let sig = rpc.send_and_confirm_transaction(&tx).await?;
+    println!("  {}", style(sig).green());
+    Ok(())
+}
+
+/// Signs and sends a durable-nonce message as built by the commands.
+async fn send_message(
+    program: &Program<&Keypair>,
+    message: Message,
+    signers: &[&Keypair],
+) -> anyhow::Result<()> {
+    let blockhash = message.recent_blockhash;
+    let tx = Transaction::new(signers, message, blockhash);
+    let sig = program
+        .async_rpc()
+        .send_and_confirm_transaction(&tx)
+        .await?;
+    println!("  {}", style(sig).green());
+    Ok(())
+}
+
+/// An owner key together with its nonce account.
+struct Owner {
+    keypair: Keypair,
+    nonce_account: AnchorPubkey,
+}
+
+impl Owner {
+    async fn signer_arg(&self, program: &Program<&Keypair>) -> anyhow::Result<SignerArg> {
+        Ok(SignerArg::new(
+            self.keypair.pubkey(),
+            self.nonce_account,
+            current_nonce(program, &self.nonce_account).await?,
+        ))
+    }
+}
+
+pub async fn run(program: &Program<&Keypair>) -> anyhow::Result<()> {
+    let rpc = program.async_rpc();
+
+    let mut owners = Vec::new();
+    for _ in 0..2 {
+        let keypair = Keypair::new();
+        step(&format!("Funding owner {}", keypair.pubkey()));
+        let sig = rpc
+            .request_airdrop(&keypair.pubkey(), AIRDROP_LAMPORTS)
+            .await?;
+        rpc.poll_for_signature(&sig).await?;
+
+        let nonce = Keypair::new();
+        step(&format!("Creating nonce account {}", nonce.pubkey()));
+        let lamports = rpc
+            .get_minimum_balance_for_rent_exemption(NonceState::size())
+            .await?;
+        let instructions = system_instruction::create_nonce_account(
+            &keypair.pubkey(),
+            &nonce.pubkey(),
+            &keypair.pubkey(),
+            lamports,
+        );
+        send_instructions(program, &instructions, &[&keypair, &nonce]).await?;
+        owners.push(Owner {
+            keypair,
+            nonce_account: nonce.pubkey(),
+        });
+    }
+    let [proposer, approver] = &owners[..] else {
+        unreachable!("two owners were created");
+    };
+
+    let multisig = Keypair::new();
+    step(&format!("Creating 2 of 2 multisig {}", multisig.pubkey()));
+    let message = create_multisig_message(
+        program,
+        false,
+        &proposer.signer_arg(program).await?,
+        owners.iter().map(|owner| owner.keypair.pubkey()).collect(),
+        2,
+        multisig.pubkey(),
+    )
+    .await?;
+    send_message(program, message, &[&proposer.keypair, &multisig]).await?;
+    let (multisig_pda, _) = derive_multisig_signer(&multisig.pubkey(), &program.id());
+
+    let (mint, from, to) = (Keypair::new(), Keypair::new(), Keypair::new());
+    step(&format!(
+        "Funding the multisig token account {} of mint {}",
+        from.pubkey(),
+        mint.pubkey()
+    ));
+    let token_program: AnchorPubkey = spl_token::id().to_bytes().into();
+    let mint_rent = rpc
+        .get_minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN)
+        .await?;
+    let account_rent = rpc
+        .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)
+        .await?;
+    let payer = proposer.keypair.pubkey();
+    let mut instructions = vec![
+        system_instruction::create_account(
+            &payer,
+            &mint.pubkey(),
+            mint_rent,
+            spl_token::state::Mint::LEN as u64,
+            &token_program,
+        ),
+        to_anchor_instruction(token_instruction::initialize_mint2(
+            &spl_token::id(),
+            &mint.pubkey().to_bytes().into(),
+            &payer.to_bytes().into(),
+            None,
+            DECIMALS,
+        )?),
+    ];
+    for (account, owner) in [(&from, multisig_pda), (&to, approver.keypair.pubkey())] {
+        instructions.push(system_instruction::create_account(
+            &payer,
+            &account.pubkey(),
+            account_rent,
+            spl_token::state::Account::LEN as u64,
+            &token_program,
+        ));
+        instructions.push(to_anchor_instruction(
+            token_instruction::initialize_account3(
+                &spl_token::id(),
+                &account.pubkey().to_bytes().into(),
+                &mint.pubkey().to_bytes().into(),
+                &owner.to_bytes().into(),
+            )?,
+        ));
+    }
+    instructions.push(to_anchor_instruction(token_instruction::mint_to(
+        &spl_token::id(),
+        &mint.pubkey().to_bytes().into(),
+        &from.pubkey().to_bytes().into(),
+        &payer.to_bytes().into(),
+        &[],
+        MINTED_AMOUNT,
+    )?));
+    send_instructions(
+        program,
+        &instructions,
+        &[&proposer.keypair, &mint, &from, &to],
+    )
+    .await?;
+
+    let transaction = Keypair::new();
+    step(&format!(
+        "Proposing a transfer to {} as transaction {}",
+        to.pubkey(),
+        transaction.pubkey()
+    ));
+    let transfer = token_instruction::transfer(
+        &spl_token::id(),
+        &from.pubkey().to_bytes().into(),
+        &to.pubkey().to_bytes().into(),
+        &multisig_pda.to_bytes().into(),
+        &[],
+        TRANSFER_AMOUNT,
+    )?;
+    let message = proposal_message(
+        program,
+        false,
+        &proposer.signer_arg(program).await?,
+        multisig.pubkey(),
+        transfer,
+        transaction.pubkey(),
+        vec![],
+    )
+    .await?;
+    send_message(program, message, &[&proposer.keypair, &transaction]).await?;
+
+    step(&format!("Approving by {}", approver.keypair.pubkey()));
+    let message = approve_message(
+        program,
+        &approver.signer_arg(program).await?,
+        multisig.pubkey(),
+        transaction.pubkey(),
+    )?;
+    send_message(program, message, &[&approver.keypair]).await?;
+    let approvals = fetch_approval_count(program, transaction.pubkey()).await?;
+    if !approvals.is_met() {
+        return Err(anyhow::Error::msg(format!(
+            "expected the transaction to be approved, got {} of {} approvals",
+            approvals.approved, approvals.threshold
+        )));
+    }
+
+    step("Executing the transfer");
+    let transaction_account: coral_multisig::Transaction =
+        program.account(transaction.pubkey()).await?;
+    let message = execute_message(
+        program,
+        &proposer.signer_arg(program).await?,
+        multisig.pubkey(),
+        transaction.pubkey(),
+        &transaction_account,
+        &ComputeBudgetArgs::default(),
+    )?;
+    send_message(program, message, &[&proposer.keypair]).await?;
+
+    let received = fetch_token_account(
+        program,
+        &to.pubkey(),
+        "destination",
+        &mut MintDecimals::new(&[]),
+    )
+    .await?
+    .amount;
+    if received != TRANSFER_AMOUNT {
+        return Err(anyhow::Error::msg(format!(
+            "expected the destination to receive {} base units, got {}",
+            TRANSFER_AMOUNT, received
+        )));
+    }
+    println!("{}", style("Self-test passed").green().bold());
+    Ok(())
+}
@@ -0,0 +1,205 @@
+//! Signing sessions, the interchange format of the offline multi-party flow:
+//! a message together with its required signers and the signatures collected
+//! so far. Built messages are written as sessions with `--session`,
+//! `AddSignatures` collects signatures into them and `Submit --from-session`
+//! sends them once complete.
+//!
+//! Schema of version 1, a JSON object with:
+//!
+//! - `version`: `1`;
+//! - `message`: base64 message to sign;
+//! - `message_hash`: base58 SHA-256 of the serialized message;
+//! - `signers`: required signers in the order of their signatures, each with
+//!   - `pubkey`: base58 public key,
+//!   - `roles`: `signer`, `fee payer` and `nonce authority`,
+//!   - `signature`: base58 signature, or `null` while it is missing,
+//!   - `nonce`: for the nonce authority, `account` and `value` of the durable
+//!     nonce the message advances, otherwise `null`.
+
+use std::path::Path;
+
+use anchor_client::{
+    anchor_lang::solana_program::hash,
+    solana_sdk::{
+        message::Message, pubkey::Pubkey, sanitize::Sanitize, signature::Signature,
+        transaction::Transaction,
+    },
+};
+use base64::{engine::Engine, prelude::BASE64_STANDARD};
+use serde::{Deserialize, Serialize};
+
+use crate::error::SerumError;
+use crate::{durable_nonce_account, required_signers};
+
+pub const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+pub struct SigningSession {
+    version: u32,
+    message: String,
+    message_hash: String,
+    signers: Vec<SessionSigner>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionSigner {
+    pubkey: String,
+    roles: Vec<String>,
+    signature: Option<String>,
+    nonce: Option<SessionNonce>,
+}
+
+/// Durable nonce advanced by the message, under the signer's authority.
+#[derive(Serialize, Deserialize)]
+struct SessionNonce {
+    account: String,
+    value: String,
+}
+
+fn invalid(what: &str) -> anyhow::Error {
+    SerumError::Validation(format!("invalid signing session: {}", what)).into()
+}
+
+impl SigningSession {
+    /// Opens a session for `message` with the signatures already made by
+    /// `signatures`' signers.
+    pub fn new(message: &Message, signatures: &[(Pubkey, Signature)]) -> Self {
+        let signers = required_signers(message)
+            .into_iter()
+            .map(|(key, roles)| SessionSigner {
+                pubkey: key.to_string(),
+                signature: signatures
+                    .iter()
+                    .find(|(signer, _)| *signer == key)
+                    .map(|(_, signature)| signature.to_string()),
+                nonce: durable_nonce_account(message)
+                    .filter(|_| roles.contains(&"nonce authority"))
+                    .map(|account| SessionNonce {
+                        account: account.to_string(),
+                        value: message.recent_blockhash.to_string(),
+                    }),
+                roles: roles.into_iter().map(str::to_string).collect(),
+            })
+            .collect();
+        let serialized = message.serialize();
+        Self {
+            version: VERSION,
+            message: BASE64_STANDARD.encode(&serialized),
+            message_hash: hash::hash(&serialized).to_string(),
+            signers,
+        }
+    }
+
+    /// Reads a session, checking that it is consistent with its message.
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            anyhow::Error::msg(format!("failed to read session {}: {}", path.display(), e))
+        })?;
+        let session: Self = serde_json::from_str(&content)?;
+        if session.version != VERSION {
+            return Err(invalid(&format!("unsupported version {}", session.version)));
+        }
+        let message = session.message()?;
+        if hash::hash(&message.serialize()).to_string() != session.message_hash {
+            return Err(invalid("the message doesn't match its hash"));
+        }
+        let required = &message.account_keys[..message.header.num_required_signatures as usize];
+        if required.len() != session.signers.len()
+            || required
+                .iter()
+                .zip(&session.signers)
+                .any(|(key, listed)| key.to_string() != listed.pubkey)
+        {
+            return Err(invalid("the listed signers don't match the message"));
+        }
+        for (key, signature) in required.iter().zip(session.signatures()?) {
+            if let Some(signature) = signature {
+                if !signature.verify(key.as_ref(), &message.serialize()) {
+                    return Err(invalid(&format!("the signature of {} doesn't verify", key)));
+                }
+            }
+        }
+        Ok(session)
+    }
+
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// File name identifying the session by its message hash.
+    pub fn file_name(&self) -> String {
+        format!("signing-session-{}.json", &self.message_hash[..8])
+    }
+
+    pub fn message(&self) -> anyhow::Result<Message> {
+        let data = BASE64_STANDARD
+            .decode(&self.message)
+            .map_err(|_| invalid("the message is not base64"))?;
+        let message: Message =
+            bincode::deserialize(&data).map_err(|_| invalid("the message can't be decoded"))?;
+        message
+            .sanitize()
+            .map_err(|e| invalid(&format!("malformed message: {}", e)))?;
+        Ok(message)
+    }
+
+    fn signatures(&self) -> anyhow::Result<Vec<Option<Signature>>> {
+        self.signers
+            .iter()
+            .map(|signer| {
+                signer
+                    .signature
+                    .as_deref()
+                    .map(|signature| {
+                        signature.parse().map_err(|_| {
+                            invalid(&format!("malformed signature of {}", signer.pubkey))
+                        })
+                    })
+                    .transpose()
+            })
+            .collect()
+    }
+
+    /// Signers whose signature is still missing.
+    pub fn pending(&self) -> Vec<&str> {
+        self.signers
+            .iter()
+            .filter(|signer| signer.signature.is_none())
+            .map(|signer| signer.pubkey.as_str())
+            .collect()
+    }
+
+    /// Fills the slot of the signer `signature` verifies for, returning the signer.
+    pub fn add_signature(&mut self, signature: Signature) -> anyhow::Result<Pubkey> {
+        let message = self.message()?.serialize();
+        for signer in &mut self.signers {
+            let pubkey: Pubkey = signer.pubkey.parse()?;
+            if signature.verify(pubkey.as_ref(), &message) {
+                signer.signature = Some(signature.to_string());
+                return Ok(pubkey);
+            }
+        }
+        Err(SerumError::Validation(format!(
+            "signature {} doesn't verify for any signer of the session",
+            signature
+        ))
+        .into())
+    }
+
+    /// Assembles the transaction, once every signature is collected.
+    pub fn to_transaction(&self) -> anyhow::Result<Transaction> {
+        let pending = self.pending();
+        if !pending.is_empty() {
+            return Err(SerumError::Validation(format!(
+                "the session still misses the signatures of {}",
+                pending.join(", ")
+            ))
+            .into());
+        }
+        Ok(Transaction {
+            signatures: self.signatures()?.into_iter().flatten().collect(),
+            message: self.message()?,
+        })
+    }
+}
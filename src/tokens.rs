@@ -0,0 +1,106 @@
+//! Well-known mainnet tokens, so that mints can be given by their symbol on
+//! mainnet.
+
+use anchor_client::solana_sdk::pubkey::Pubkey;
+
+use crate::error::SerumError;
+
+struct Token {
+    symbol: &'static str,
+    mint: &'static str,
+    decimals: u8,
+}
+
+const TOKENS: &[Token] = &[
+    Token {
+        symbol: "USDC",
+        mint: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+        decimals: 6,
+    },
+    Token {
+        symbol: "USDT",
+        mint: "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB",
+        decimals: 6,
+    },
+    Token {
+        symbol: "wSOL",
+        mint: "So11111111111111111111111111111111111111112",
+        decimals: 9,
+    },
+    Token {
+        symbol: "mSOL",
+        mint: "mSoLzYCxHdYgdzU16g5QSh3i5K3z3KZK7ytfQcJm7So",
+        decimals: 9,
+    },
+    Token {
+        symbol: "stSOL",
+        mint: "7dHbWXmci3dT8UFYWYZweBLXgycu7Y3iL6trKn1Y7ARj",
+        decimals: 9,
+    },
+    Token {
+        symbol: "JUP",
+        mint: "JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN",
+        decimals: 6,
+    },
+    Token {
+        symbol: "BONK",
+        mint: "DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263",
+        decimals: 5,
+    },
+];
+
+/// Mint given as an address, or as the symbol of a well-known token, which
+/// only names its mint on mainnet.
+#[derive(Clone, Copy)]
+pub struct MintArg {
+    mint: Pubkey,
+    symbol: Option<&'static str>,
+}
+
+impl MintArg {
+    /// Mint on the cluster, refusing symbols outside mainnet.
+    pub fn resolve(self, mainnet: bool) -> anyhow::Result<Pubkey> {
+        match self.symbol {
+            Some(symbol) if !mainnet => Err(SerumError::Validation(format!(
+                "{} names the mainnet mint {}, pass the address of the mint on this cluster",
+                symbol, self.mint
+            ))
+            .into()),
+            _ => Ok(self.mint),
+        }
+    }
+}
+
+/// Parses a mint address, or the symbol of a well-known token.
+pub fn parse_mint(s: &str) -> anyhow::Result<MintArg> {
+    match TOKENS
+        .iter()
+        .find(|token| token.symbol.eq_ignore_ascii_case(s))
+    {
+        Some(token) => Ok(MintArg {
+            mint: token.mint.parse()?,
+            symbol: Some(token.symbol),
+        }),
+        None => s
+            .parse()
+            .map(|mint| MintArg { mint, symbol: None })
+            .map_err(|_| {
+                anyhow::Error::msg(format!(
+                    "not a mint address or a known symbol ({})",
+                    TOKENS
+                        .iter()
+                        .map(|token| token.symbol)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+            }),
+    }
+}
+
+/// Decimals of a well-known mainnet mint.
+pub fn decimals(mint: &Pubkey) -> Option<u8> {
+    TOKENS
+        .iter()
+        .find(|token| token.mint == mint.to_string())
+        .map(|token| token.decimals)
+}